@@ -0,0 +1,272 @@
+//! Random values and templates for property tests, behind the `arbitrary`
+//! feature.
+//!
+//! Lengths and counts favour their boundaries: empty, one, the largest the
+//! template allows, and the edges between tiers, since that is where
+//! encoders tend to break.
+
+use template::Template;
+use template_ids::TemplateId;
+use template_kind::TemplateKind;
+use encoders::fixed::Fixed;
+use encoders::dynamic::Dynamic;
+use encoders::dlist::Dlist;
+use encoders::ilist::Ilist;
+use encoders::tuple::{Tuple, Field};
+use encoders::etuple::Etuple;
+use encoders::defaulted::Defaulted;
+use encoders::choice::Choice;
+use encoders::optional::Optional;
+use nest::Nest;
+use rand::Rng;
+
+/// Caps on how big generated values get, on top of the template's own
+/// limits, so Gamma and Delta tiers do not produce gigabytes.
+#[derive(Debug, Clone, Copy)]
+pub struct Generator {
+    /// Longest Dynamic value generated.
+    pub max_bytes: usize,
+    /// Most elements generated for a list.
+    pub max_items: usize,
+    /// Deepest nesting of generated templates.
+    pub max_depth: usize
+}
+
+impl Default for Generator {
+    fn default() -> Generator {
+        Generator {
+            max_bytes: 300,
+            max_items: 8,
+            max_depth: 3
+        }
+    }
+}
+
+/// A random value of `template` from the default `Generator`.
+pub fn random_nest<R: Rng + ?Sized>(template: &Template, rng: &mut R) -> Nest<'static> {
+    Generator::default().nest(template, rng)
+}
+
+impl Generator {
+    pub fn nest<R: Rng + ?Sized>(&self, template: &Template, rng: &mut R) -> Nest<'static> {
+        // Etuples limit the total size of their fields, which random fields
+        // can exceed, so try a few times before settling for the default.
+        for _ in 0..8 {
+            let nest = self.try_nest(template, rng);
+            if template.encoded_len(&nest).is_ok() {
+                return nest;
+            }
+        }
+        template.default_nest()
+    }
+    fn try_nest<R: Rng + ?Sized>(&self, template: &Template, rng: &mut R) -> Nest<'static> {
+        match template.kind() {
+            TemplateKind::Fixed { length } => Nest::OwnedBytes(random_bytes(length, rng)),
+            TemplateKind::Dynamic { max_length, .. } => {
+                let length = pick_length(max_length.min(self.max_bytes), rng);
+                Nest::OwnedBytes(random_bytes(length, rng))
+            },
+            TemplateKind::Dlist { max_length, template, .. } | TemplateKind::Ilist { max_length, template, .. } => {
+                let length = pick_length(max_length.min(self.max_items), rng);
+                Nest::OwnedNests((0..length).map(|_| self.nest(template, rng)).collect())
+            },
+            TemplateKind::Tuple { fields } => {
+                Nest::OwnedNests(fields.iter().map(|field| self.nest(&field.template, rng)).collect())
+            },
+            TemplateKind::Etuple { fields, .. } => {
+                let mut nests = fields.iter().map(|field| self.nest(&field.template, rng)).collect::<Vec<_>>();
+                if rng.gen_ratio(1, 8) {
+                    let length = rng.gen_range(1..=8);
+                    nests.push(Nest::OwnedBytes(random_bytes(length, rng)));
+                }
+                Nest::OwnedNests(nests)
+            },
+            TemplateKind::Choice { variants } => {
+                let variant = rng.gen_range(0..variants.len());
+                Nest::OwnedNests(vec![
+                    Nest::OwnedBytes(vec![variant as u8]),
+                    self.nest(&variants[variant].template, rng)
+                ])
+            },
+            TemplateKind::Optional { template } => {
+                if rng.gen_bool(0.5) {
+                    Nest::OwnedNests(vec![self.nest(template, rng)])
+                } else {
+                    Nest::OwnedNests(vec![])
+                }
+            }
+        }
+    }
+    /// A random template of any kind, nested at most `max_depth` deep.
+    pub fn template<R: Rng + ?Sized>(&self, rng: &mut R) -> Template {
+        self.template_at(self.max_depth, rng)
+    }
+    fn template_at<R: Rng + ?Sized>(&self, depth: usize, rng: &mut R) -> Template {
+        // Some templates have no value that fits, such as an Etuple<=255
+        // of two fixed(200), so keep only those whose default encodes.
+        loop {
+            let template = self.try_template_at(depth, rng);
+            if template.encoded_len(&template.default_nest()).is_ok() {
+                return template;
+            }
+        }
+    }
+    fn try_template_at<R: Rng + ?Sized>(&self, depth: usize, rng: &mut R) -> Template {
+        let kinds = if depth == 0 { 2 } else { 9 };
+        match rng.gen_range(0..kinds) {
+            0 => {
+                let length = pick_length(self.max_bytes.clamp(1, 65792), rng).max(1);
+                let template_id = if length > 256 { TemplateId::FixedBeta } else { TemplateId::FixedAlpha };
+                Template::new(Box::new(Fixed::new(template_id, length).unwrap()))
+            },
+            1 => Template::new(Box::new(Dynamic::new(pick_tier(TemplateId::DynamicAlpha, rng)).unwrap())),
+            2 => {
+                let template = self.template_at(depth - 1, rng);
+                Template::new(Box::new(Dlist::new(pick_tier(TemplateId::DlistAlpha, rng), template).unwrap()))
+            },
+            3 => {
+                let template = self.template_at(depth - 1, rng);
+                Template::new(Box::new(Ilist::new(pick_tier(TemplateId::IlistAlpha, rng), template).unwrap()))
+            },
+            4 => Template::new(Box::new(Tuple::new(self.fields(depth - 1, false, rng)).unwrap())),
+            5 => {
+                let template_id = pick_tier(TemplateId::EtupleAlpha, rng);
+                Template::new(Box::new(Etuple::new(template_id, self.fields(depth - 1, true, rng)).unwrap()))
+            },
+            6 => Template::new(Box::new(Choice::new(self.fields(depth - 1, false, rng)).unwrap())),
+            7 => Template::new(Box::new(Optional::new(self.template_at(depth - 1, rng)))),
+            _ => {
+                let template = self.template_at(depth - 1, rng);
+                let default = self.nest(&template, rng);
+                Template::new(Box::new(Defaulted::new(template, default).unwrap()))
+            }
+        }
+    }
+    fn fields<R: Rng + ?Sized>(&self, depth: usize, defaults: bool, rng: &mut R) -> Vec<Field> {
+        let length = rng.gen_range(1..=4);
+        let first_default = if defaults { rng.gen_range(0..=length) } else { length };
+        (0..length).map(|index| {
+            let mut field = Field::new(self.template_at(depth, rng));
+            if rng.gen_bool(0.5) {
+                field.name = Some(format!("f{}", index));
+            }
+            if rng.gen_ratio(1, 4) {
+                field.doc = Some(format!("Field \"{}\"", index));
+            }
+            if index >= first_default {
+                field.default = Some(self.nest(&field.template, rng));
+            }
+            field
+        }).collect()
+    }
+}
+
+/// One of the four tiers of the kind whose Alpha tier is `alpha`.
+fn pick_tier<R: Rng + ?Sized>(alpha: TemplateId, rng: &mut R) -> TemplateId {
+    let tiers = match alpha {
+        TemplateId::DynamicAlpha => [TemplateId::DynamicAlpha, TemplateId::DynamicBeta, TemplateId::DynamicGamma, TemplateId::DynamicDelta],
+        TemplateId::DlistAlpha => [TemplateId::DlistAlpha, TemplateId::DlistBeta, TemplateId::DlistGamma, TemplateId::DlistDelta],
+        TemplateId::IlistAlpha => [TemplateId::IlistAlpha, TemplateId::IlistBeta, TemplateId::IlistGamma, TemplateId::IlistDelta],
+        _ => [TemplateId::EtupleAlpha, TemplateId::EtupleBeta, TemplateId::EtupleGamma, TemplateId::EtupleDelta]
+    };
+    tiers[rng.gen_range(0..4)]
+}
+
+/// A length up to `max_length`, half the time one of the boundaries.
+fn pick_length<R: Rng + ?Sized>(max_length: usize, rng: &mut R) -> usize {
+    if rng.gen_bool(0.5) {
+        return rng.gen_range(0..=max_length);
+    }
+    let boundaries = [0, 1, 255, 256, 257, 65535, 65536, max_length];
+    loop {
+        let length = boundaries[rng.gen_range(0..boundaries.len())];
+        if length <= max_length {
+            return length;
+        }
+    }
+}
+
+fn random_bytes<R: Rng + ?Sized>(length: usize, rng: &mut R) -> Vec<u8> {
+    match rng.gen_range(0..4) {
+        0 => vec![0; length],
+        1 => vec![255; length],
+        _ => {
+            let mut bytes = vec![0; length];
+            rng.fill(&mut bytes[..]);
+            bytes
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template::Template;
+    use arbitrary::{random_nest, Generator};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn assert_round_trips(schema: &str) {
+        let template = Template::from_schema(schema).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..200 {
+            let nest = random_nest(&template, &mut rng);
+            let encoding = template.encode(&nest).unwrap();
+            assert_eq!(template.validate(&encoding), Ok(encoding.len()));
+            assert_eq!(template.decode(&encoding).unwrap(), nest);
+        }
+    }
+
+    #[test]
+    fn should_generate_valid_nests() {
+        assert_round_trips("fixed(1)");
+        assert_round_trips("fixed(257)");
+        assert_round_trips("dynamic<=255");
+        assert_round_trips("dynamic<=4294967295");
+        assert_round_trips("dlist<=255(dynamic<=65535)");
+        assert_round_trips("ilist<=255(dynamic<=255)");
+        assert_round_trips("tuple(fixed(2),dlist<=255(tuple(dynamic<=255)))");
+        assert_round_trips("etuple<=255(fixed(1),dynamic<=255,fixed(1)=0x00)");
+        assert_round_trips("default(dynamic<=255,0x0101)");
+        assert_round_trips("choice(a:fixed(1),dlist<=255(dynamic<=255))");
+        assert_round_trips("optional(tuple(fixed(1),optional(dynamic<=255)))");
+    }
+
+    #[test]
+    fn should_reach_boundaries() {
+        let template = Template::from_schema("dynamic<=255").unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+        let lengths = (0..200)
+            .map(|_| random_nest(&template, &mut rng).bytes().len())
+            .collect::<Vec<_>>();
+        assert!(lengths.contains(&0));
+        assert!(lengths.contains(&1));
+        assert!(lengths.contains(&255));
+        assert!(lengths.iter().all(|length| *length <= 255));
+    }
+
+    #[test]
+    fn should_generate_every_kind() {
+        let generator = Generator::default();
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut names = (0..500)
+            .map(|_| generator.template(&mut rng).to_schema().split(['(', '<']).next().unwrap().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        assert_eq!(names, vec!["choice", "default", "dlist", "dynamic", "etuple", "fixed", "ilist", "optional", "tuple"]);
+    }
+
+    #[test]
+    fn should_respect_generator_caps() {
+        let template = Template::from_schema("dlist<=65535(dynamic<=65535)").unwrap();
+        let generator = Generator { max_bytes: 4, max_items: 2, ..Generator::default() };
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..100 {
+            let nest = generator.nest(&template, &mut rng);
+            assert!(nest.iter_nests().len() <= 2);
+            assert!(nest.iter_nests().all(|child| child.bytes().len() <= 4));
+        }
+    }
+
+}
@@ -0,0 +1,298 @@
+//! Checks whether values written with one template can be read with another,
+//! for catching wire format breaks when a template changes.
+
+use template::Template;
+use template_kind::TemplateKind;
+use encoders::tuple::Field;
+use std::fmt;
+
+const TIER_NAMES: [&str; 4] = ["Alpha", "Beta", "Gamma", "Delta"];
+
+/// One reason encodings from one template do not decode under another.
+/// `path` names the value, with `[*]` standing for every list element.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Incompatibility {
+    pub path: String,
+    pub description: String
+}
+
+impl fmt::Display for Incompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.description, self.path)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// Why bytes written with the old template do not decode with the new one.
+    pub old_to_new: Vec<Incompatibility>,
+    /// Why bytes written with the new template do not decode with the old one.
+    pub new_to_old: Vec<Incompatibility>
+}
+
+impl Report {
+    pub fn is_backward_compatible(&self) -> bool {
+        self.old_to_new.is_empty()
+    }
+    pub fn is_forward_compatible(&self) -> bool {
+        self.new_to_old.is_empty()
+    }
+}
+
+pub fn check(old: &Template, new: &Template) -> Report {
+    Report {
+        old_to_new: check_direction(old, new),
+        new_to_old: check_direction(new, old)
+    }
+}
+
+/// Lists why encodings made with `from` do not decode with `to`.
+pub fn check_direction(from: &Template, to: &Template) -> Vec<Incompatibility> {
+    let mut incompatibilities = vec![];
+    check_at(&mut String::new(), from, to, &mut incompatibilities);
+    incompatibilities
+}
+
+fn title(name: &str) -> String {
+    name[..1].to_uppercase() + &name[1..]
+}
+
+fn push(incompatibilities: &mut Vec<Incompatibility>, path: &str, description: String) {
+    incompatibilities.push(Incompatibility {
+        path: if path.is_empty() { "[]".to_string() } else { path.to_string() },
+        description
+    });
+}
+
+fn push_field(path: &mut String, index: usize, field: &Field) {
+    match field.name {
+        Some(ref name) => {
+            path.push('.');
+            path.push_str(name);
+        },
+        None => path.push_str(&format!("[{}]", index))
+    }
+}
+
+fn tier_name(length_encoding_length: usize) -> &'static str {
+    TIER_NAMES[length_encoding_length - 1]
+}
+
+fn tier_change(length_encoding_length: usize, other_length_encoding_length: usize) -> &'static str {
+    if other_length_encoding_length < length_encoding_length { "narrowed" } else { "widened" }
+}
+
+fn check_at(path: &mut String, from: &Template, to: &Template, incompatibilities: &mut Vec<Incompatibility>) {
+    match (from.kind(), to.kind()) {
+        (TemplateKind::Fixed { length }, TemplateKind::Fixed { length: other_length }) => {
+            if length != other_length {
+                push(incompatibilities, path, format!("Fixed length changed {}->{}", length, other_length));
+            }
+        },
+        (
+            TemplateKind::Dynamic { length_encoding_length, .. },
+            TemplateKind::Dynamic { length_encoding_length: other_length_encoding_length, .. }
+        ) => {
+            if length_encoding_length != other_length_encoding_length {
+                push(incompatibilities, path, format!(
+                    "Dynamic tier {} from {} to {}",
+                    tier_change(length_encoding_length, other_length_encoding_length),
+                    tier_name(length_encoding_length),
+                    tier_name(other_length_encoding_length)
+                ));
+            }
+        },
+        (
+            TemplateKind::Dlist { length_encoding_length, template, .. },
+            TemplateKind::Dlist { length_encoding_length: other_length_encoding_length, template: other_template, .. }
+        ) | (
+            TemplateKind::Ilist { length_encoding_length, template, .. },
+            TemplateKind::Ilist { length_encoding_length: other_length_encoding_length, template: other_template, .. }
+        ) => {
+            if length_encoding_length != other_length_encoding_length {
+                push(incompatibilities, path, format!(
+                    "{} tier {} from {} to {}",
+                    title(from.kind().name()),
+                    tier_change(length_encoding_length, other_length_encoding_length),
+                    tier_name(length_encoding_length),
+                    tier_name(other_length_encoding_length)
+                ));
+            }
+            let length = path.len();
+            path.push_str("[*]");
+            check_at(path, template, other_template, incompatibilities);
+            path.truncate(length);
+        },
+        (TemplateKind::Tuple { fields }, TemplateKind::Tuple { fields: other_fields }) => {
+            if fields.len() != other_fields.len() {
+                push(incompatibilities, path, format!("Tuple fields changed {}->{}", fields.len(), other_fields.len()));
+            }
+            for (index, (field, other_field)) in fields.iter().zip(other_fields.iter()).enumerate() {
+                let length = path.len();
+                push_field(path, index, field);
+                check_at(path, &field.template, &other_field.template, incompatibilities);
+                path.truncate(length);
+            }
+        },
+        (
+            TemplateKind::Etuple { length_encoding_length, fields, .. },
+            TemplateKind::Etuple { length_encoding_length: other_length_encoding_length, fields: other_fields, .. }
+        ) => {
+            if length_encoding_length != other_length_encoding_length {
+                push(incompatibilities, path, format!(
+                    "Etuple tier {} from {} to {}",
+                    tier_change(length_encoding_length, other_length_encoding_length),
+                    tier_name(length_encoding_length),
+                    tier_name(other_length_encoding_length)
+                ));
+            }
+            // Fields `to` does not know are kept as unknown data, but fields
+            // `from` never wrote need a default.
+            for (index, other_field) in other_fields.iter().enumerate() {
+                let length = path.len();
+                match fields.get(index) {
+                    Some(field) => {
+                        push_field(path, index, field);
+                        if field.default.is_some() && other_field.default.is_none() {
+                            push(incompatibilities, path, "Etuple field default removed".to_string());
+                        }
+                        check_at(path, &field.template, &other_field.template, incompatibilities);
+                    },
+                    None => {
+                        push_field(path, index, other_field);
+                        if other_field.default.is_none() {
+                            push(incompatibilities, path, "Etuple field added without a default".to_string());
+                        }
+                    }
+                }
+                path.truncate(length);
+            }
+        },
+        (TemplateKind::Optional { template }, TemplateKind::Optional { template: other_template }) => {
+            let length = path.len();
+            path.push_str("[0]");
+            check_at(path, template, other_template, incompatibilities);
+            path.truncate(length);
+        },
+        (TemplateKind::Choice { variants }, TemplateKind::Choice { variants: other_variants }) => {
+            // `to` can read every variant it has, so only removed variants
+            // break decoding.
+            if other_variants.len() < variants.len() {
+                push(incompatibilities, path, format!("Choice variants removed {}->{}", variants.len(), other_variants.len()));
+            }
+            for (variant, other_variant) in variants.iter().zip(other_variants.iter()) {
+                let length = path.len();
+                push_field(path, 1, variant);
+                check_at(path, &variant.template, &other_variant.template, incompatibilities);
+                path.truncate(length);
+            }
+        },
+        (kind, other_kind) => {
+            push(incompatibilities, path, format!("Changed from {} to {}", kind.name(), other_kind.name()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template::Template;
+    use compat::{check, check_direction, Incompatibility};
+
+    fn incompatibility(description: &str, path: &str) -> Incompatibility {
+        Incompatibility { path: path.to_string(), description: description.to_string() }
+    }
+
+    fn check_schemas(old: &str, new: &str) -> (Vec<String>, Vec<String>) {
+        let report = check(&Template::from_schema(old).unwrap(), &Template::from_schema(new).unwrap());
+        (
+            report.old_to_new.iter().map(|incompatibility| incompatibility.to_string()).collect(),
+            report.new_to_old.iter().map(|incompatibility| incompatibility.to_string()).collect()
+        )
+    }
+
+    #[test]
+    fn should_accept_identical_templates() {
+        let schema = "tuple(id:fixed(32),dlist<=255(ilist<=65535(dynamic<=255)))";
+        let report = check(&Template::from_schema(schema).unwrap(), &Template::from_schema(schema).unwrap());
+        assert!(report.is_backward_compatible());
+        assert!(report.is_forward_compatible());
+    }
+
+    #[test]
+    fn should_ignore_names() {
+        let (old_to_new, new_to_old) = check_schemas("tuple(a:fixed(1))", "tuple(b\"doc\":fixed(1))");
+        assert!(old_to_new.is_empty());
+        assert!(new_to_old.is_empty());
+    }
+
+    #[test]
+    fn should_report_fixed_lengths() {
+        let (old_to_new, new_to_old) = check_schemas("tuple(fixed(1),fixed(32))", "tuple(fixed(1),fixed(20))");
+        assert_eq!(old_to_new, vec!["Fixed length changed 32->20 at [1]"]);
+        assert_eq!(new_to_old, vec!["Fixed length changed 20->32 at [1]"]);
+    }
+
+    #[test]
+    fn should_report_tiers() {
+        let (old_to_new, new_to_old) = check_schemas("dlist<=255(dynamic<=65535)", "dlist<=65535(dynamic<=255)");
+        assert_eq!(old_to_new, vec![
+            "Dlist tier widened from Alpha to Beta at []",
+            "Dynamic tier narrowed from Beta to Alpha at [*]"
+        ]);
+        assert_eq!(new_to_old, vec![
+            "Dlist tier narrowed from Beta to Alpha at []",
+            "Dynamic tier widened from Alpha to Beta at [*]"
+        ]);
+    }
+
+    #[test]
+    fn should_allow_appending_etuple_fields_with_defaults() {
+        let (old_to_new, new_to_old) = check_schemas(
+            "etuple<=255(id:fixed(1))",
+            "etuple<=255(id:fixed(1),flags:fixed(1)=0x00)"
+        );
+        assert!(old_to_new.is_empty());
+        assert!(new_to_old.is_empty());
+        let (old_to_new, new_to_old) = check_schemas(
+            "etuple<=255(id:fixed(1),flags:fixed(1)=0x00)",
+            "etuple<=65535(id:fixed(1),flags:fixed(1),extra:dynamic<=255)"
+        );
+        assert_eq!(old_to_new, vec![
+            "Etuple tier widened from Alpha to Beta at []",
+            "Etuple field default removed at .flags",
+            "Etuple field added without a default at .extra"
+        ]);
+        assert_eq!(new_to_old, vec!["Etuple tier narrowed from Beta to Alpha at []"]);
+    }
+
+    #[test]
+    fn should_allow_appending_choice_variants() {
+        let (old_to_new, new_to_old) = check_schemas(
+            "choice(circle:fixed(1),square:fixed(2))",
+            "choice(circle:fixed(1),square:fixed(4),dynamic<=255)"
+        );
+        assert_eq!(old_to_new, vec!["Fixed length changed 2->4 at .square"]);
+        assert_eq!(new_to_old, vec!["Choice variants removed 3->2 at []", "Fixed length changed 4->2 at .square"]);
+    }
+
+    #[test]
+    fn should_check_optional_values() {
+        let (old_to_new, _) = check_schemas("tuple(a:optional(fixed(1)))", "tuple(a:optional(fixed(2)))");
+        assert_eq!(old_to_new, vec!["Fixed length changed 1->2 at .a[0]"]);
+        let (old_to_new, _) = check_schemas("optional(fixed(1))", "fixed(1)");
+        assert_eq!(old_to_new, vec!["Changed from optional to fixed at []"]);
+    }
+
+    #[test]
+    fn should_report_structure() {
+        let old = Template::from_schema("tuple(id:fixed(1),items:dlist<=255(fixed(2)))").unwrap();
+        let new = Template::from_schema("tuple(id:dynamic<=255,items:ilist<=255(fixed(2)),fixed(1))").unwrap();
+        assert_eq!(check_direction(&old, &new), vec![
+            incompatibility("Tuple fields changed 2->3", "[]"),
+            incompatibility("Changed from fixed to dynamic", ".id"),
+            incompatibility("Changed from dlist to ilist", ".items")
+        ]);
+    }
+
+}
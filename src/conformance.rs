@@ -0,0 +1,168 @@
+//! Round-trip checks over random templates and values, behind the
+//! `arbitrary` feature. Each failure is reported with the template's schema
+//! so it can be reproduced.
+
+use template::Template;
+use nest::Nest;
+use arbitrary::Generator;
+use rand::Rng;
+use std::panic::{self, AssertUnwindSafe};
+
+/// Checks `templates` random templates with `rounds` random values each.
+pub fn run<R: Rng + ?Sized>(generator: &Generator, rng: &mut R, templates: usize, rounds: usize) -> Result<(), String> {
+    for _ in 0..templates {
+        let template = generator.template(rng);
+        check_template(generator, &template, rng, rounds)?;
+    }
+    Ok(())
+}
+
+/// Checks that `template` survives a jinyang round trip, that random values
+/// survive encoding and patching, and that reading mutations of the jinyang
+/// and decoding or patching mutations of the encodings returns rather than
+/// panics.
+pub fn check_template<R: Rng + ?Sized>(generator: &Generator, template: &Template, rng: &mut R, rounds: usize) -> Result<(), String> {
+    let schema = template.to_schema();
+    let fail = |message: String| Err(format!("{}: {}", schema, message));
+    let jinyang = template.export_jinyang();
+    let parsed = match Template::from_jinyang_with_remainder(&jinyang) {
+        Ok((parsed, [])) => parsed,
+        Ok(_) => return fail("from_jinyang left a remainder".to_string()),
+        Err(error) => return fail(format!("from_jinyang failed with {:?}", error))
+    };
+    if parsed.export_jinyang() != jinyang {
+        return fail("jinyang changed after from_jinyang".to_string());
+    }
+    if parsed.fingerprint() != template.fingerprint() {
+        return fail("fingerprint changed after from_jinyang".to_string());
+    }
+    for _ in 0..rounds {
+        let mutated = mutate(&jinyang, rng);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            if let Ok((template, _)) = Template::from_jinyang_with_remainder(&mutated) {
+                let _ = template.export_jinyang();
+                let _ = template.to_schema();
+            }
+        }));
+        if result.is_err() {
+            return fail(format!("from_jinyang of {:?} panicked", mutated));
+        }
+        let nest = generator.nest(template, rng);
+        let encoding = match template.encode(&nest) {
+            Ok(encoding) => encoding,
+            Err(error) => return fail(format!("encode failed with {:?} for {:?}", error, nest))
+        };
+        if template.encoded_len(&nest) != Ok(encoding.len()) {
+            return fail(format!("encoded_len does not match encode for {:?}", nest));
+        }
+        if parsed.decode(&encoding).as_ref() != Ok(&nest) {
+            return fail(format!("decode does not return {:?}", nest));
+        }
+        if template.validate(&encoding) != Ok(encoding.len()) || template.skip(&encoding) != Ok(encoding.len()) {
+            return fail(format!("validate or skip does not measure {:?}", nest));
+        }
+        let mut reader = &encoding[..];
+        if template.decode_from_reader(&mut reader) != Ok(Some(nest.clone())) {
+            return fail(format!("decode_from_reader does not return {:?}", nest));
+        }
+        let mut streamed = vec![];
+        if template.encode_to_writer(&nest, &mut streamed).is_err() || streamed != encoding {
+            return fail(format!("encode_to_writer does not match encode for {:?}", nest));
+        }
+        let (path, child_template) = random_path(template, &nest, rng);
+        let replacement = generator.nest(child_template, rng);
+        let mut patched = encoding.clone();
+        if template.patch(&mut patched, &path, &replacement).is_ok() {
+            match template.decode(&patched) {
+                Ok(decoded) => if decoded.get(&path) != Ok(&replacement) {
+                    return fail(format!("patch at {:?} does not write {:?}", path, replacement));
+                },
+                Err(error) => return fail(format!("decode after patch at {:?} failed with {:?}", path, error))
+            }
+        }
+        let mutated = mutate(&encoding, rng);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _ = template.patch(&mut mutated.clone(), &path, &replacement);
+            let _ = template.decode(&mutated);
+            let _ = template.validate(&mutated);
+            let _ = template.skip(&mutated);
+            let _ = template.decode_from_reader(&mut &mutated[..]);
+            if let Ok(lazy) = template.decode_lazy(&mutated) {
+                let _ = lazy.to_nest();
+            }
+        }));
+        if result.is_err() {
+            return fail(format!("decoding or patching {:?} panicked", mutated));
+        }
+    }
+    Ok(())
+}
+
+/// A random path into `nest`, and the template of the value it leads to.
+fn random_path<'t, R: Rng + ?Sized>(template: &'t Template, nest: &Nest, rng: &mut R) -> (Vec<usize>, &'t Template) {
+    let mut path = vec![];
+    let mut template = template;
+    let mut nest = nest;
+    while !nest.is_bytes() && nest.iter_nests().len() > 0 && rng.gen_bool(0.7) {
+        let index = rng.gen_range(0..nest.iter_nests().len());
+        match template.kind().nest_child_template(nest, index) {
+            Some(child_template) => {
+                path.push(index);
+                template = child_template;
+                nest = nest.child(index).unwrap();
+            },
+            None => break
+        }
+    }
+    (path, template)
+}
+
+/// Flips, inserts, removes or truncates bytes of `encoding`.
+fn mutate<R: Rng + ?Sized>(encoding: &[u8], rng: &mut R) -> Vec<u8> {
+    let mut mutated = encoding.to_vec();
+    for _ in 0..rng.gen_range(1..=4) {
+        let position = rng.gen_range(0..=mutated.len());
+        match rng.gen_range(0..4) {
+            0 if position < mutated.len() => mutated[position] ^= 1 << rng.gen_range(0..8),
+            1 if position < mutated.len() => mutated[position] = rng.gen(),
+            2 => mutated.insert(position, rng.gen()),
+            _ => mutated.truncate(position)
+        }
+    }
+    mutated
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template::Template;
+    use arbitrary::Generator;
+    use conformance::{run, check_template};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn should_pass_for_random_templates() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(run(&Generator::default(), &mut rng, 300, 20), Ok(()));
+    }
+
+    #[test]
+    fn should_pass_for_every_kind() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for schema in &[
+            "fixed(65792)",
+            "dynamic<=16777215",
+            "dlist<=4294967295(tuple(a:fixed(1),dynamic<=255))",
+            "ilist<=65535(ilist<=255(dynamic<=255))",
+            "etuple<=65535(a:fixed(1),b\"doc\":dynamic<=255=0x0100)",
+            "default(dlist<=255(fixed(2)),0x010001)",
+            "choice(a:fixed(1),b:etuple<=255(dynamic<=255))",
+            "optional(ilist<=255(optional(fixed(1))))"
+        ] {
+            let template = Template::from_schema(schema).unwrap();
+            assert_eq!(check_template(&Generator::default(), &template, &mut rng, 100), Ok(()));
+        }
+    }
+
+}
@@ -0,0 +1,250 @@
+use error::Error;
+use traits::encoder::Encoder;
+use template_ids::TemplateId;
+use encoders::tuple::{
+    Field,
+    check_fields,
+    fields_from_jinyang_with_remainder,
+    metadata_from_jinyang_with_remainder,
+    export_metadata_to
+};
+use std::io::{Read, Write};
+use std::any::Any;
+use nest::Nest;
+use size_bounds::SizeBounds;
+use patch::LengthField;
+use template_kind::TemplateKind;
+
+/// The variant a Choice value is set to, when `nest` has the shape of one:
+/// a single variant byte, then the value.
+pub fn nest_variant(nest: &Nest) -> Option<usize> {
+    if nest.is_bytes() || nest.iter_nests().len() != 2 {
+        return None;
+    }
+    match nest.child(0).and_then(|variant| variant.try_bytes()) {
+        Ok(&[variant]) => Some(variant as usize),
+        _ => None
+    }
+}
+
+/// One of several variants, encoded as the index of the variant in one
+/// byte and then the variant's value. Values are two children: the index as
+/// `Bytes`, then the value.
+///
+/// The jinyang is the Tuple's, with variants in place of fields, so
+/// variants can have names and docs the same way. Variants cannot have
+/// defaults; the default value is the first variant with its default.
+pub struct Choice {
+    variants: Vec<Field>
+}
+
+impl Choice {
+    pub fn new(variants: Vec<Field>) -> Result<Choice, Error> {
+        check_fields(&variants)?;
+        if variants.iter().any(|variant| variant.default.is_some()) {
+            return Err(Error::choice__new__variants_should_not_have_defaults);
+        }
+        Ok(Choice { variants })
+    }
+    pub fn from_jinyang_with_remainder(_template_id: TemplateId, jinyang: &[u8], depth: usize) -> Result<(Choice, &[u8]), Error> {
+        let (mut variants, remainder) = fields_from_jinyang_with_remainder(jinyang, depth)?;
+        let remainder = metadata_from_jinyang_with_remainder(&mut variants, remainder)?;
+        Ok((Choice::new(variants)?, remainder))
+    }
+    pub fn variants(&self) -> &[Field] {
+        &self.variants
+    }
+    pub fn variant_index(&self, name: &str) -> Option<usize> {
+        self.variants.iter().position(|variant| variant.name.as_deref() == Some(name))
+    }
+    /// The variant of `nest` and its value.
+    fn split_nest<'b, 'a>(&self, nest: &'b Nest<'a>) -> Result<(usize, &'b Nest<'a>), Error> {
+        let variant = nest_variant(nest).ok_or(Error::choice__encode_to__nest_should_be_variant_and_value)?;
+        if variant >= self.variants.len() {
+            return Err(Error::choice__encode_to__variant_should_be_lt_variants_length);
+        }
+        Ok((variant, nest.child(1)?))
+    }
+    /// The variant at the start of `bytes`.
+    fn variant(&self, bytes: &[u8]) -> Result<&Field, Error> {
+        let variant = *bytes.first().ok_or(Error::choice__decode_with_remainder__bytes_length_should_be_gte_1)?;
+        self.variants.get(variant as usize).ok_or(Error::choice__decode_with_remainder__variant_should_be_lt_variants_length)
+    }
+}
+
+impl Encoder for Choice {
+    fn template_id(&self) -> u8 {
+        TemplateId::Choice as u8
+    }
+    fn kind(&self) -> TemplateKind<'_> {
+        TemplateKind::Choice {
+            variants: &self.variants
+        }
+    }
+    fn encode_to(&self, nest: &Nest, to: &mut Vec<u8>) -> Result<(), Error> {
+        let (variant, value) = self.split_nest(nest)?;
+        to.push(variant as u8);
+        self.variants[variant].template.encoder().encode_to(value, to)
+    }
+    fn write_to(&self, nest: &Nest, to: &mut dyn Write) -> Result<(), Error> {
+        let (variant, value) = self.split_nest(nest)?;
+        to.write_all(&[variant as u8]).map_err(|error| Error::encoder__write_to__io_error(error.kind()))?;
+        self.variants[variant].template.encoder().write_to(value, to)
+    }
+    fn encoded_len(&self, nest: &Nest) -> Result<usize, Error> {
+        let (variant, value) = self.split_nest(nest)?;
+        Ok(1 + self.variants[variant].template.encoder().encoded_len(value)?)
+    }
+    fn size_bounds(&self) -> SizeBounds {
+        let mut min = usize::MAX;
+        let mut max = Some(0);
+        for variant in &self.variants {
+            let bounds = variant.template.size_bounds();
+            min = min.min(bounds.min);
+            max = max.and_then(|max: usize| bounds.max.map(|variant_max| max.max(variant_max)));
+        }
+        SizeBounds {
+            min: 1 + min,
+            max: max.and_then(|max| max.checked_add(1)),
+            payload_offset: Some(1)
+        }
+    }
+    fn default_nest(&self) -> Nest<'static> {
+        Nest::OwnedNests(vec![Nest::OwnedBytes(vec![0]), self.variants[0].template.default_nest()])
+    }
+    fn read_from(&self, from: &mut dyn Read) -> Result<Nest<'static>, Error> {
+        let mut variant = [0];
+        from.read_exact(&mut variant).map_err(Error::from_read_error)?;
+        let value = self.variant(&variant)?.template.encoder().read_from(from)?;
+        Ok(Nest::OwnedNests(vec![Nest::OwnedBytes(variant.to_vec()), value]))
+    }
+    fn decode_with_remainder<'a>(&self, bytes: &'a [u8]) -> Result<(Nest<'a>, &'a [u8]), Error> {
+        let variant = self.variant(bytes)?;
+        let (value, remainder) = variant.template.encoder().decode_with_remainder(&bytes[1..])?;
+        Ok((Nest::OwnedNests(vec![Nest::Bytes(&bytes[..1]), value]), remainder))
+    }
+    fn validate(&self, bytes: &[u8]) -> Result<usize, Error> {
+        Ok(1 + self.variant(bytes)?.template.encoder().validate(&bytes[1..])?)
+    }
+    fn skip(&self, bytes: &[u8]) -> Result<usize, Error> {
+        Ok(1 + self.variant(bytes)?.template.encoder().skip(&bytes[1..])?)
+    }
+    fn locate(&self, encoding: &[u8], start: usize, path: &[usize], length_fields: &mut Vec<LengthField>) -> Result<(&dyn Encoder, usize), Error> {
+        if start > encoding.len() {
+            return Err(Error::encoder__locate__start_should_be_lte_encoding_length);
+        }
+        if path.is_empty() {
+            return Ok((self, start));
+        }
+        // The variant byte has to match the value after it, so only the value
+        // can be located.
+        if path[0] != 1 {
+            return Err(Error::choice__locate__index_should_be_1);
+        }
+        self.variant(&encoding[start..])?.template.encoder().locate(encoding, start + 1, &path[1..], length_fields)
+    }
+    fn export_jinyang_to(&self, to: &mut Vec<u8>) {
+        to.push(self.variants.len() as u8);
+        for variant in &self.variants {
+            variant.template.export_jinyang_to(to);
+        }
+        export_metadata_to(&self.variants, to);
+    }
+    fn export_canonical_jinyang_to(&self, to: &mut Vec<u8>) {
+        to.push(self.variants.len() as u8);
+        for variant in &self.variants {
+            variant.template.export_canonical_jinyang_to(to);
+        }
+        to.push(0);
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template::Template;
+    use encoders::choice::Choice;
+    use encoders::tuple::Field;
+    use error::Error;
+    use nest::Nest;
+    use fixtures::fixed;
+
+    fn shape() -> Template {
+        Template::from_schema("choice(circle:fixed(1),square\"Side length\":dynamic<=255)").unwrap()
+    }
+
+    #[test]
+    fn should_encode_decode() {
+        let nest = Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[2, 3])]);
+        assert_eq!(shape().encode(&nest), Ok(vec![1, 2, 2, 3]));
+        assert_eq!(shape().decode(&[1, 2, 2, 3]), Ok(nest));
+        assert_eq!(shape().encode(&Nest::Nests(&[&Nest::Bytes(&[0]), &Nest::Bytes(&[7])])), Ok(vec![0, 7]));
+        assert_eq!(shape().validate(&[0, 7, 9]), Ok(2));
+        assert_eq!(shape().skip(&[1, 1, 5, 9]), Ok(3));
+        assert_eq!(shape().size_bounds().min, 2);
+        assert_eq!(shape().size_bounds().max, Some(257));
+        let mut reader = &[1u8, 1, 5][..];
+        assert_eq!(shape().decode_from_reader(&mut reader), Ok(Some(Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[5])]))));
+    }
+
+    #[test]
+    fn should_patch_value() {
+        let mut encoding = vec![1, 1, 5];
+        assert_eq!(shape().patch(&mut encoding, &[1], &Nest::Bytes(&[6, 7])), Ok(()));
+        assert_eq!(encoding, vec![1, 2, 6, 7]);
+        assert_eq!(
+            shape().patch(&mut encoding, &[0], &Nest::Bytes(&[0])).err(),
+            Some(Error::choice__locate__index_should_be_1)
+        );
+        assert_eq!(encoding, vec![1, 2, 6, 7]);
+    }
+
+    #[test]
+    fn should_jinyang_variant_names() {
+        let jinyang = shape().export_jinyang();
+        assert_eq!(jinyang, vec![
+            20, 2, 0, 0, 2, 1,
+            6, b'c', b'i', b'r', b'c', b'l', b'e', 0, 0,
+            6, b's', b'q', b'u', b'a', b'r', b'e', 11, 0, b'S', b'i', b'd', b'e', b' ', b'l', b'e', b'n', b'g', b't', b'h'
+        ]);
+        let parsed = Template::from_jinyang(&jinyang).unwrap();
+        let choice : &Choice = parsed.encoder().as_any().downcast_ref().unwrap();
+        assert_eq!(choice.variants()[1].doc, Some("Side length".to_string()));
+        assert_eq!(choice.variant_index("square"), Some(1));
+        assert_eq!(parsed.export_jinyang(), jinyang);
+        assert_eq!(shape().export_canonical_jinyang(), vec![20, 2, 0, 0, 2, 0]);
+    }
+
+    #[test]
+    fn should_name_variant_paths() {
+        let template = Template::from_schema("dlist<=255(choice(circle:fixed(1),square:tuple(side:fixed(1))))").unwrap();
+        let nest = Nest::Nests(&[&Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Nests(&[&Nest::Bytes(&[4])])])]);
+        assert_eq!(template.nest_path_to_string(&nest, &[0, 1, 0]), "[0].square.side");
+        assert_eq!(template.path_to_string(&[0, 1, 0]), "[0][1][0]");
+    }
+
+    #[test]
+    fn should_error_when_invalid() {
+        assert_eq!(
+            Choice::new(vec![Field::new(fixed(1)).with_default(Nest::OwnedBytes(vec![0]))]).err(),
+            Some(Error::choice__new__variants_should_not_have_defaults)
+        );
+        assert_eq!(
+            shape().encode(&Nest::Nests(&[&Nest::Bytes(&[2]), &Nest::Bytes(&[0])])).err(),
+            Some(Error::choice__encode_to__variant_should_be_lt_variants_length)
+        );
+        assert_eq!(
+            shape().encode(&Nest::Nests(&[&Nest::Bytes(&[0, 0]), &Nest::Bytes(&[0])])).err(),
+            Some(Error::choice__encode_to__nest_should_be_variant_and_value)
+        );
+        assert_eq!(shape().decode(&[]).err(), Some(Error::choice__decode_with_remainder__bytes_length_should_be_gte_1));
+        assert_eq!(
+            shape().validate(&[2, 0]).err(),
+            Some(Error::choice__decode_with_remainder__variant_should_be_lt_variants_length)
+        );
+    }
+
+}
@@ -0,0 +1,152 @@
+use error::Error;
+use traits::encoder::Encoder;
+use template::Template;
+use std::io::{Read, Write};
+use std::any::Any;
+use nest::Nest;
+use size_bounds::SizeBounds;
+use patch::LengthField;
+use template_kind::TemplateKind;
+use template_ids::TemplateId;
+
+/// Gives a template an explicit default. Everything else, including `kind`,
+/// is the wrapped template's, so a Defaulted template encodes the same
+/// bytes and fits anywhere the wrapped one does.
+///
+/// The jinyang is the wrapped template's jinyang, then the default encoded
+/// with it. Defaults have no effect on encodings, so the canonical jinyang
+/// is the wrapped template's.
+pub struct Defaulted {
+    template: Template,
+    default: Nest<'static>
+}
+
+impl Defaulted {
+    pub fn new(template: Template, default: Nest<'static>) -> Result<Defaulted, Error> {
+        template.encode(&default)?;
+        Ok(Defaulted { template, default })
+    }
+    pub fn from_jinyang_with_remainder(_template_id: TemplateId, jinyang: &[u8], depth: usize) -> Result<(Defaulted, &[u8]), Error> {
+        let (template, remainder) = Template::from_jinyang_at_depth(jinyang, depth + 1)?;
+        let (default, remainder) = template.decode_with_remainder(remainder)?;
+        let default = default.to_owned_nest();
+        Ok((Defaulted::new(template, default)?, remainder))
+    }
+    pub fn template(&self) -> &Template {
+        &self.template
+    }
+}
+
+impl Encoder for Defaulted {
+    fn template_id(&self) -> u8 {
+        TemplateId::Defaulted as u8
+    }
+    fn kind(&self) -> TemplateKind<'_> {
+        self.template.kind()
+    }
+    fn encode_to(&self, nest: &Nest, to: &mut Vec<u8>) -> Result<(), Error> {
+        self.template.encoder().encode_to(nest, to)
+    }
+    fn write_to(&self, nest: &Nest, to: &mut dyn Write) -> Result<(), Error> {
+        self.template.encoder().write_to(nest, to)
+    }
+    fn encoded_len(&self, nest: &Nest) -> Result<usize, Error> {
+        self.template.encoded_len(nest)
+    }
+    fn size_bounds(&self) -> SizeBounds {
+        self.template.size_bounds()
+    }
+    fn default_nest(&self) -> Nest<'static> {
+        self.default.clone()
+    }
+    fn read_from(&self, from: &mut dyn Read) -> Result<Nest<'static>, Error> {
+        self.template.encoder().read_from(from)
+    }
+    fn decode_with_remainder<'a>(&self, bytes: &'a [u8]) -> Result<(Nest<'a>, &'a [u8]), Error> {
+        self.template.decode_with_remainder(bytes)
+    }
+    fn validate(&self, bytes: &[u8]) -> Result<usize, Error> {
+        self.template.validate(bytes)
+    }
+    fn skip(&self, bytes: &[u8]) -> Result<usize, Error> {
+        self.template.skip(bytes)
+    }
+    fn locate(&self, encoding: &[u8], start: usize, path: &[usize], length_fields: &mut Vec<LengthField>) -> Result<(&dyn Encoder, usize), Error> {
+        self.template.encoder().locate(encoding, start, path, length_fields)
+    }
+    fn export_jinyang_to(&self, to: &mut Vec<u8>) {
+        self.template.export_jinyang_to(to);
+        self.template.encoder().encode_to(&self.default, to).unwrap();
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template::Template;
+    use encoders::defaulted::Defaulted;
+    use error::Error;
+    use nest::Nest;
+
+    #[test]
+    fn should_default_every_kind() {
+        assert_eq!(Template::from_schema("fixed(3)").unwrap().default_nest(), Nest::Bytes(&[0, 0, 0]));
+        assert_eq!(Template::from_schema("dynamic<=255").unwrap().default_nest(), Nest::Bytes(&[]));
+        assert_eq!(Template::from_schema("dlist<=255(fixed(1))").unwrap().default_nest(), Nest::Nests(&[]));
+        assert_eq!(Template::from_schema("ilist<=255(fixed(1))").unwrap().default_nest(), Nest::Nests(&[]));
+        assert_eq!(
+            Template::from_schema("tuple(fixed(1),tuple(dynamic<=255))").unwrap().default_nest(),
+            Nest::Nests(&[&Nest::Bytes(&[0]), &Nest::Nests(&[&Nest::Bytes(&[])])])
+        );
+        assert_eq!(
+            Template::from_schema("etuple<=255(fixed(1),fixed(1)=0x07)").unwrap().default_nest(),
+            Nest::Nests(&[&Nest::Bytes(&[0]), &Nest::Bytes(&[7])])
+        );
+        assert_eq!(
+            Template::from_schema("choice(tuple(fixed(2)),dynamic<=255)").unwrap().default_nest(),
+            Nest::Nests(&[&Nest::Bytes(&[0]), &Nest::Nests(&[&Nest::Bytes(&[0, 0])])])
+        );
+    }
+
+    #[test]
+    fn should_use_explicit_defaults() {
+        let template = Template::from_schema("tuple(fixed(1),default(dynamic<=255,0x020102))").unwrap();
+        assert_eq!(
+            template.default_nest(),
+            Nest::Nests(&[&Nest::Bytes(&[0]), &Nest::Bytes(&[1, 2])])
+        );
+        let nest = Nest::Nests(&[&Nest::Bytes(&[9]), &Nest::Bytes(&[3])]);
+        assert_eq!(template.encode(&nest), Ok(vec![9, 1, 3]));
+        assert_eq!(template.decode(&[9, 1, 3]), Ok(nest));
+        let template = Template::from_schema("default(choice(fixed(1),dynamic<=255),0x010105)").unwrap();
+        assert_eq!(template.default_nest(), Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[5])]));
+    }
+
+    #[test]
+    fn should_jinyang() {
+        let template = Template::from_schema("default(fixed(2),0x0102)").unwrap();
+        assert_eq!(template.export_jinyang(), vec![19, 0, 1, 1, 2]);
+        assert_eq!(template.export_canonical_jinyang(), vec![0, 1]);
+        assert_eq!(template.fingerprint(), Template::from_schema("fixed(2)").unwrap().fingerprint());
+        let parsed = Template::from_jinyang(&[19, 0, 1, 1, 2]).unwrap();
+        assert_eq!(parsed.default_nest(), Nest::Bytes(&[1, 2]));
+        assert_eq!(parsed.to_schema(), "default(fixed(2),0x0102)");
+        assert_eq!(parsed.kind().name(), "fixed");
+    }
+
+    #[test]
+    fn should_error_when_default_does_not_encode() {
+        assert_eq!(
+            Defaulted::new(Template::from_schema("fixed(2)").unwrap(), Nest::OwnedBytes(vec![1])).err(),
+            Some(Error::fixed__encode_to__bytes_length_should_match_self_length)
+        );
+        assert_eq!(
+            Template::from_jinyang_with_remainder(&[19, 0, 1, 1]).err(),
+            Some(Error::fixed__decode_with_remainder__bytes_length_should_be_gte_self_length)
+        );
+    }
+
+}
@@ -2,10 +2,13 @@ use error::Error;
 use traits::encoder::Encoder;
 use template_ids::TemplateId;
 use template::Template;
-use byteorder::{LittleEndian, WriteBytesExt, ReadBytesExt};
-use std::io::Cursor;
+use std::io::{Read, Write};
 use std::any::Any;
 use nest::Nest;
+use size_bounds::SizeBounds;
+use patch::LengthField;
+use length_prefix;
+use template_kind::TemplateKind;
 
 pub struct Dlist {
     template_id: TemplateId,
@@ -29,7 +32,7 @@ impl Dlist  {
                 Ok(Dlist{
                     template_id,
                     length_encoding_length: 2,
-                    max_length: 65536,
+                    max_length: 65535,
                     template
                 })
             },
@@ -54,13 +57,13 @@ impl Dlist  {
             }
         }
     }
-    pub fn from_jinyang_with_remainder(template_id: TemplateId, jinyang: &[u8]) -> Result<(Dlist, &[u8]), Error> {
+    pub fn from_jinyang_with_remainder(template_id: TemplateId, jinyang: &[u8], depth: usize) -> Result<(Dlist, &[u8]), Error> {
         match(template_id) {
             TemplateId::DlistAlpha
             | TemplateId::DlistBeta
             | TemplateId::DlistGamma
             | TemplateId::DlistDelta => {
-                let template_and_remainder = Template::from_jinyang_with_remainder(&jinyang)?;
+                let template_and_remainder = Template::from_jinyang_at_depth(&jinyang, depth + 1)?;
                 let dlist_result = Dlist::new(template_id, template_and_remainder.0);
                 match(dlist_result) {
                     Ok(Dlist) => Ok((Dlist, template_and_remainder.1)),
@@ -75,13 +78,27 @@ impl Dlist  {
     pub fn length_encoding_length(&self) -> usize {
         self.length_encoding_length
     }
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+    pub fn template(&self) -> &Template {
+        &self.template
+    }
     pub fn encode_length_to(&self, length: usize, to: &mut Vec<u8>) {
-        if (length > std::u32::MAX as usize) {
-            panic!();
-        }
-        let mut length_encoding = Vec::new();
-        length_encoding.write_u32::<LittleEndian>(length as u32);
-        to.extend_from_slice(&length_encoding[0..self.length_encoding_length]);
+        length_prefix::encode_length_to(length, self.length_encoding_length, to);
+    }
+    pub fn write_length_to(&self, length: usize, to: &mut dyn Write) -> Result<(), Error> {
+        length_prefix::write_length_to(length, self.length_encoding_length, to)
+    }
+    pub fn decode_length(&self, bytes: &[u8]) -> Result<usize, Error> {
+        length_prefix::decode_length(
+            bytes,
+            self.length_encoding_length,
+            Error::dynamic__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length
+        )
+    }
+    pub fn read_length_from(&self, from: &mut dyn Read) -> Result<usize, Error> {
+        length_prefix::read_length_from(from, self.length_encoding_length)
     }
 }
 
@@ -89,35 +106,323 @@ impl Encoder for Dlist {
     fn template_id(&self) -> u8 {
         self.template_id as u8
     }
+    fn kind(&self) -> TemplateKind<'_> {
+        TemplateKind::Dlist {
+            length_encoding_length: self.length_encoding_length,
+            max_length: self.max_length,
+            template: &self.template
+        }
+    }
     fn encode_to<'a>(&self, nest: &Nest, to: &'a mut Vec<u8>) -> Result<(), Error> {
-        let nests = nest.nests();
+        let nests = nest.try_nests()?;
+        if nests.len() > self.max_length {
+            return Err(Error::dlist__encode_to__nests_length_should_be_lte_max_length);
+        }
         self.encode_length_to(nests.len(), to);
         for nest in nests {
-            self.template.encode_to(nest, to);
+            self.template.encoder().encode_to(nest, to)?;
         }
         Ok(())
     }
+    fn write_to(&self, nest: &Nest, to: &mut dyn Write) -> Result<(), Error> {
+        let nests = nest.try_nests()?;
+        if nests.len() > self.max_length {
+            return Err(Error::dlist__encode_to__nests_length_should_be_lte_max_length);
+        }
+        self.write_length_to(nests.len(), to)?;
+        for nest in nests {
+            self.template.encoder().write_to(nest, to)?;
+        }
+        Ok(())
+    }
+    fn encoded_len(&self, nest: &Nest) -> Result<usize, Error> {
+        let nests = nest.try_nests()?;
+        if nests.len() > self.max_length {
+            return Err(Error::dlist__encode_to__nests_length_should_be_lte_max_length);
+        }
+        let mut length = self.length_encoding_length;
+        for nest in nests {
+            length += self.template.encoder().encoded_len(nest)?;
+        }
+        Ok(length)
+    }
+    fn size_bounds(&self) -> SizeBounds {
+        let max = self.template.encoder().size_bounds().max
+            .and_then(|max| max.checked_mul(self.max_length))
+            .and_then(|max| max.checked_add(self.length_encoding_length));
+        SizeBounds {
+            min: self.length_encoding_length,
+            max,
+            payload_offset: Some(self.length_encoding_length)
+        }
+    }
+    fn default_nest(&self) -> Nest<'static> {
+        Nest::OwnedNests(vec![])
+    }
+    fn read_from(&self, from: &mut dyn Read) -> Result<Nest<'static>, Error> {
+        let length = self.read_length_from(from)?;
+        let mut nests = Vec::new();
+        for _ in 0..length {
+            nests.push(self.template.encoder().read_from(from)?);
+        }
+        Ok(Nest::OwnedNests(nests))
+    }
     fn decode_with_remainder<'a>(&self, bytes: &'a [u8]) -> Result<(Nest<'a>, &'a [u8]), Error> {
-        if bytes.len() < self.length_encoding_length {
-            Err(Error::dynamic__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length)
-        } else {
-            let mut length_encoding = vec![0; 4];
-            for i in 0..self.length_encoding_length {
-                length_encoding[i] = bytes[i];
+        let length = self.decode_length(bytes)?;
+        let mut nests = Vec::new();
+        let mut remainder = &bytes[self.length_encoding_length..];
+        for _ in 0..length {
+            let (nest, next_remainder) = self.template.encoder().decode_with_remainder(remainder)?;
+            nests.push(nest);
+            remainder = next_remainder;
+        }
+        Ok((Nest::OwnedNests(nests), remainder))
+    }
+    fn validate(&self, bytes: &[u8]) -> Result<usize, Error> {
+        let length = self.decode_length(bytes)?;
+        let mut consumed = self.length_encoding_length;
+        for _ in 0..length {
+            consumed += self.template.encoder().validate(&bytes[consumed..])?;
+        }
+        Ok(consumed)
+    }
+    fn skip(&self, bytes: &[u8]) -> Result<usize, Error> {
+        let length = self.decode_length(bytes)?;
+        let bounds = self.template.encoder().size_bounds();
+        if bounds.is_constant() {
+            let consumed = bounds.min.checked_mul(length)
+                .and_then(|nests_length| nests_length.checked_add(self.length_encoding_length));
+            if let Some(consumed) = consumed {
+                if consumed <= bytes.len() {
+                    return Ok(consumed);
+                }
             }
-            let length = Cursor::new(&length_encoding).read_u32::<LittleEndian>().unwrap() as usize;
-            if (bytes.len() < (self.length_encoding_length + length)) {
-                Err(Error::dynamic__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length_plus_length)
-            } else {
-                Ok((
-                    Nest::Bytes(&bytes[self.length_encoding_length..self.length_encoding_length + length]),
-                    &bytes[self.length_encoding_length + length..]
-                ))
+        }
+        let mut consumed = self.length_encoding_length;
+        for _ in 0..length {
+            consumed += self.template.encoder().skip(&bytes[consumed..])?;
+        }
+        Ok(consumed)
+    }
+    fn locate(&self, encoding: &[u8], start: usize, path: &[usize], length_fields: &mut Vec<LengthField>) -> Result<(&dyn Encoder, usize), Error> {
+        if start > encoding.len() {
+            return Err(Error::encoder__locate__start_should_be_lte_encoding_length);
+        }
+        if path.is_empty() {
+            return Ok((self, start));
+        }
+        let length = self.decode_length(&encoding[start..])?;
+        if path[0] >= length {
+            return Err(Error::encoder__locate__index_should_be_lt_length);
+        }
+        let mut nest_start = start + self.length_encoding_length;
+        let bounds = self.template.encoder().size_bounds();
+        if bounds.is_constant() {
+            nest_start = match bounds.min.checked_mul(path[0] + 1) {
+                Some(nests_length) if nest_start + nests_length <= encoding.len() => nest_start + nests_length - bounds.min,
+                _ => return Err(Error::dlist__locate__bytes_length_should_be_gte_nests_length)
+            };
+        } else {
+            for _ in 0..path[0] {
+                nest_start += self.template.encoder().skip(&encoding[nest_start..])?;
             }
         }
+        self.template.encoder().locate(encoding, nest_start, &path[1..], length_fields)
+    }
+    fn export_jinyang_to<'a>(&self, to: &'a mut Vec<u8>) {
+        self.template.export_jinyang_to(to);
+    }
+    fn export_canonical_jinyang_to(&self, to: &mut Vec<u8>) {
+        self.template.export_canonical_jinyang_to(to);
     }
-    fn export_jinyang_to<'a>(&self, to: &'a mut Vec<u8>) {}
     fn as_any(&self) -> &dyn Any {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use template_ids::TemplateId;
+    use template::{Template, MAX_DEPTH};
+    use error::Error;
+    use nest::Nest;
+    use fixtures::{fixed, dynamic, dlist};
+
+    #[test]
+    fn should_encode() {
+        let template = dlist(TemplateId::DlistAlpha, dynamic(TemplateId::DynamicAlpha));
+        assert_eq!(
+            template.encode(&Nest::Nests(&[
+                &Nest::Bytes(&[1, 2]),
+                &Nest::Bytes(&[])
+            ])),
+            Ok(vec![2, 2, 1, 2, 0])
+        );
+    }
+
+    #[test]
+    fn should_measure_encoded_len() {
+        let template = dlist(TemplateId::DlistGamma, dynamic(TemplateId::DynamicAlpha));
+        let nest = Nest::Nests(&[
+            &Nest::Bytes(&[1, 2]),
+            &Nest::Bytes(&[]),
+            &Nest::Bytes(&[3])
+        ]);
+        assert_eq!(template.encoded_len(&nest), Ok(9));
+        assert_eq!(template.encode(&nest).unwrap().len(), 9);
+        assert_eq!(
+            template.encoded_len(&Nest::Nests(&[&Nest::Bytes(&[1; 256])])),
+            Err(Error::dynamic__encode_to__bytes_length_should_be_lte_max_length)
+        );
+    }
+
+    #[test]
+    fn should_encode_to_writer() {
+        let template = dlist(TemplateId::DlistBeta, fixed(2));
+        let nest = Nest::Nests(&[
+            &Nest::Bytes(&[1, 2]),
+            &Nest::Bytes(&[3, 4])
+        ]);
+        let mut written = vec![];
+        assert_eq!(template.encode_to_writer(&nest, &mut written), Ok(()));
+        assert_eq!(written, vec![2, 0, 1, 2, 3, 4]);
+        assert_eq!(Ok(written), template.encode(&nest));
+    }
+
+    #[test]
+    fn should_error_when_encode_invalid_child() {
+        let template = dlist(TemplateId::DlistAlpha, fixed(2));
+        let nest = Nest::Nests(&[
+            &Nest::Bytes(&[1, 2]),
+            &Nest::Bytes(&[3])
+        ]);
+        assert_eq!(
+            template.encode(&nest),
+            Err(Error::fixed__encode_to__bytes_length_should_match_self_length)
+        );
+        assert_eq!(
+            template.encode_to_writer(&nest, &mut vec![]),
+            Err(Error::fixed__encode_to__bytes_length_should_match_self_length)
+        );
+    }
+
+    #[test]
+    fn should_error_when_encode_wrong_shape() {
+        let template = dlist(TemplateId::DlistAlpha, fixed(1));
+        assert_eq!(template.encode(&Nest::Bytes(&[1])), Err(Error::nest__try_nests__should_be_nests));
+        assert_eq!(
+            template.encode(&Nest::Nests(&[&Nest::Nests(&[])])),
+            Err(Error::nest__try_bytes__should_be_bytes)
+        );
+        assert_eq!(template.encoded_len(&Nest::Bytes(&[1])), Err(Error::nest__try_nests__should_be_nests));
+    }
+
+    #[test]
+    fn should_error__encode_to__nests_length_should_be_lte_max_length() {
+        let template = dlist(TemplateId::DlistAlpha, fixed(1));
+        let child = Nest::Bytes(&[1]);
+        let children = vec![&child; 256];
+        assert_eq!(
+            template.encode(&Nest::Nests(&children)),
+            Err(Error::dlist__encode_to__nests_length_should_be_lte_max_length)
+        );
+        assert_eq!(
+            template.encode_to_writer(&Nest::Nests(&children), &mut vec![]),
+            Err(Error::dlist__encode_to__nests_length_should_be_lte_max_length)
+        );
+    }
+
+    #[test]
+    fn should_decode() {
+        let template = dlist(TemplateId::DlistAlpha, dynamic(TemplateId::DynamicAlpha));
+        assert_eq!(
+            template.decode(&[2, 2, 1, 2, 0]),
+            Ok(Nest::Nests(&[
+                &Nest::Bytes(&[1, 2]),
+                &Nest::Bytes(&[])
+            ]))
+        );
+        assert_eq!(
+            template.decode(&[2, 2, 1, 2]),
+            Err(Error::dynamic__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length)
+        );
+    }
+
+    #[test]
+    fn should_validate() {
+        let template = dlist(
+            TemplateId::DlistAlpha,
+            dlist(TemplateId::DlistAlpha, dynamic(TemplateId::DynamicAlpha))
+        );
+        let encoding = [2, 1, 2, 1, 2, 0, 9];
+        assert_eq!(template.validate(&encoding), Ok(6));
+        assert_eq!(
+            template.decode_with_remainder(&encoding).map(|(_, remainder)| remainder.len()),
+            Ok(1)
+        );
+        assert_eq!(
+            template.validate(&encoding[..4]),
+            Err(Error::dynamic__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length_plus_length)
+        );
+        assert_eq!(
+            template.validate(&encoding[..5]),
+            Err(Error::dynamic__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length)
+        );
+    }
+
+    #[test]
+    fn should_skip() {
+        let fixed_template = dlist(TemplateId::DlistBeta, fixed(2));
+        assert_eq!(fixed_template.skip(&[2, 0, 1, 2, 3, 4, 5]), Ok(6));
+        assert_eq!(
+            fixed_template.skip(&[2, 0, 1, 2, 3]),
+            Err(Error::fixed__decode_with_remainder__bytes_length_should_be_gte_self_length)
+        );
+        assert_eq!(
+            fixed_template.skip(&[255, 255, 255, 255]),
+            Err(Error::fixed__decode_with_remainder__bytes_length_should_be_gte_self_length)
+        );
+
+        let dynamic_template = dlist(TemplateId::DlistAlpha, dynamic(TemplateId::DynamicAlpha));
+        assert_eq!(dynamic_template.skip(&[3, 0, 2, 1, 2, 1, 3, 9]), Ok(7));
+    }
+
+    #[test]
+    fn should_decode_from_reader() {
+        let template = dlist(TemplateId::DlistBeta, fixed(2));
+        let mut reader = &[2, 0, 1, 2, 3, 4, 0, 0][..];
+        assert_eq!(
+            template.decode_from_reader(&mut reader),
+            Ok(Some(Nest::Nests(&[
+                &Nest::Bytes(&[1, 2]),
+                &Nest::Bytes(&[3, 4])
+            ])))
+        );
+        assert_eq!(template.decode_from_reader(&mut reader), Ok(Some(Nest::Nests(&[]))));
+        assert_eq!(template.decode_from_reader(&mut reader), Ok(None));
+        assert_eq!(
+            template.decode_from_reader(&mut &[2, 0, 1, 2, 3][..]),
+            Err(Error::encoder__read_from__unexpected_eof)
+        );
+    }
+
+    #[test]
+    fn should_error_when_jinyang_nests_too_deep() {
+        let mut jinyang = vec![6; MAX_DEPTH];
+        jinyang.push(2);
+        assert!(Template::from_jinyang(&jinyang).is_ok());
+        jinyang.insert(0, 6);
+        assert_eq!(
+            Template::from_jinyang(&jinyang).err(),
+            Some(Error::template__from_jinyang__depth_should_be_lte_max_depth)
+        );
+        let mut jinyang = vec![6; 200000];
+        jinyang.push(2);
+        assert_eq!(
+            Template::from_jinyang(&jinyang).err(),
+            Some(Error::template__from_jinyang__depth_should_be_lte_max_depth)
+        );
+    }
+
+}
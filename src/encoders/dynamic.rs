@@ -1,10 +1,14 @@
 use error::Error;
 use traits::encoder::Encoder;
 use template_ids::TemplateId;
-use byteorder::{LittleEndian, WriteBytesExt, ReadBytesExt};
-use std::io::Cursor;
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{Cursor, Read, Write};
 use std::any::Any;
 use nest::Nest;
+use size_bounds::SizeBounds;
+use patch::LengthField;
+use length_prefix;
+use template_kind::TemplateKind;
 
 pub struct Dynamic {
     template_id: TemplateId,
@@ -26,7 +30,7 @@ impl Dynamic  {
                 Ok(Dynamic{
                     template_id,
                     length_encoding_length: 2,
-                    max_length: 65536
+                    max_length: 65535
                 })
             },
             TemplateId::DynamicGamma => {
@@ -68,13 +72,24 @@ impl Dynamic  {
     pub fn length_encoding_length(&self) -> usize {
         self.length_encoding_length
     }
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
     pub fn encode_length_to(&self, length: usize, to: &mut Vec<u8>) {
-        if (length > std::u32::MAX as usize) {
-            panic!();
-        }
-        let mut length_encoding = Vec::new();
-        length_encoding.write_u32::<LittleEndian>(length as u32);
-        to.extend_from_slice(&length_encoding[0..self.length_encoding_length]);
+        length_prefix::encode_length_to(length, self.length_encoding_length, to);
+    }
+    pub fn write_length_to(&self, length: usize, to: &mut dyn Write) -> Result<(), Error> {
+        length_prefix::write_length_to(length, self.length_encoding_length, to)
+    }
+    pub fn decode_length(&self, bytes: &[u8]) -> Result<usize, Error> {
+        length_prefix::decode_length(
+            bytes,
+            self.length_encoding_length,
+            Error::dynamic__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length
+        )
+    }
+    pub fn read_length_from(&self, from: &mut dyn Read) -> Result<usize, Error> {
+        length_prefix::read_length_from(from, self.length_encoding_length)
     }
 }
 
@@ -82,8 +97,14 @@ impl Encoder for Dynamic {
     fn template_id(&self) -> u8 {
         self.template_id as u8
     }
+    fn kind(&self) -> TemplateKind<'_> {
+        TemplateKind::Dynamic {
+            length_encoding_length: self.length_encoding_length,
+            max_length: self.max_length
+        }
+    }
     fn encode_to<'a>(&self, nest: &Nest, to: &'a mut Vec<u8>) -> Result<(), Error> {
-        let bytes = nest.bytes();
+        let bytes = nest.try_bytes()?;
         if bytes.len() > self.max_length {
             Err(Error::dynamic__encode_to__bytes_length_should_be_lte_max_length)
         } else {
@@ -92,6 +113,43 @@ impl Encoder for Dynamic {
             Ok(())
         }
     }
+    fn write_to(&self, nest: &Nest, to: &mut dyn Write) -> Result<(), Error> {
+        let bytes = nest.try_bytes()?;
+        if bytes.len() > self.max_length {
+            Err(Error::dynamic__encode_to__bytes_length_should_be_lte_max_length)
+        } else {
+            self.write_length_to(bytes.len(), to)?;
+            to.write_all(bytes).map_err(|error| Error::encoder__write_to__io_error(error.kind()))
+        }
+    }
+    fn encoded_len(&self, nest: &Nest) -> Result<usize, Error> {
+        let length = nest.try_bytes()?.len();
+        if length > self.max_length {
+            Err(Error::dynamic__encode_to__bytes_length_should_be_lte_max_length)
+        } else {
+            Ok(self.length_encoding_length + length)
+        }
+    }
+    fn size_bounds(&self) -> SizeBounds {
+        SizeBounds {
+            min: self.length_encoding_length,
+            max: self.length_encoding_length.checked_add(self.max_length),
+            payload_offset: Some(self.length_encoding_length)
+        }
+    }
+    fn default_nest(&self) -> Nest<'static> {
+        Nest::OwnedBytes(vec![])
+    }
+    fn read_from(&self, from: &mut dyn Read) -> Result<Nest<'static>, Error> {
+        let length = self.read_length_from(from)?;
+        let mut bytes = Vec::new();
+        (&mut *from).take(length as u64).read_to_end(&mut bytes).map_err(Error::from_read_error)?;
+        if bytes.len() < length {
+            Err(Error::encoder__read_from__unexpected_eof)
+        } else {
+            Ok(Nest::OwnedBytes(bytes))
+        }
+    }
     fn decode_with_remainder<'a>(&self, bytes: &'a [u8]) -> Result<(Nest<'a>, &'a [u8]), Error> {
         if bytes.len() < self.length_encoding_length {
             Err(Error::dynamic__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length)
@@ -111,6 +169,27 @@ impl Encoder for Dynamic {
             }
         }
     }
+    fn validate(&self, bytes: &[u8]) -> Result<usize, Error> {
+        let length = self.decode_length(bytes)?;
+        if bytes.len() - self.length_encoding_length < length {
+            Err(Error::dynamic__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length_plus_length)
+        } else {
+            Ok(self.length_encoding_length + length)
+        }
+    }
+    fn skip(&self, bytes: &[u8]) -> Result<usize, Error> {
+        self.validate(bytes)
+    }
+    fn locate(&self, encoding: &[u8], start: usize, path: &[usize], _length_fields: &mut Vec<LengthField>) -> Result<(&dyn Encoder, usize), Error> {
+        if start > encoding.len() {
+            return Err(Error::encoder__locate__start_should_be_lte_encoding_length);
+        }
+        if path.is_empty() {
+            Ok((self, start))
+        } else {
+            Err(Error::encoder__locate__path_should_end_at_bytes)
+        }
+    }
     fn export_jinyang_to<'a>(&self, to: &'a mut Vec<u8>) {}
     fn as_any(&self) -> &dyn Any {
         self
@@ -126,6 +205,7 @@ mod tests {
     use traits::encoder::Encoder;
     use error::Error;
     use nest::Nest;
+    use std::io::{self, BufReader, ErrorKind, Read};
 
     fn create_template_then<F>(
         template_id: TemplateId,
@@ -454,6 +534,16 @@ mod tests {
         // });
     }
 
+    #[test]
+    fn should_error_when_beta_length_does_not_fit_length_encoding() {
+        create_template_then(TemplateId::DynamicBeta, |template| {
+            assert_eq!(
+                template.encode(&Nest::Bytes(&[1; 65536])),
+                Err(Error::dynamic__encode_to__bytes_length_should_be_lte_max_length)
+            )
+        });
+    }
+
     #[test]
     fn should_error__encode_to__bytes_length_should_be_lte_max_length() {
         create_template_then(TemplateId::DynamicAlpha, |template| {
@@ -534,6 +624,113 @@ mod tests {
         });
     }
 
+    #[test]
+    fn should_measure_encoded_len() {
+        create_template_then(TemplateId::DynamicGamma, |template| {
+            assert_eq!(template.encoded_len(&Nest::Bytes(&[])), Ok(3));
+            assert_eq!(template.encoded_len(&Nest::Bytes(&[1; 300])), Ok(303));
+        });
+        create_template_then(TemplateId::DynamicAlpha, |template| {
+            assert_eq!(
+                template.encoded_len(&Nest::Bytes(&[1; 256])),
+                Err(Error::dynamic__encode_to__bytes_length_should_be_lte_max_length)
+            );
+        });
+    }
+
+    #[test]
+    fn should_encode_to_writer() {
+        create_template_then(TemplateId::DynamicBeta, |template| {
+            let mut written = vec![];
+            assert_eq!(template.encode_to_writer(&Nest::Bytes(&[1; 256]), &mut written), Ok(()));
+            assert_eq!(Ok(written), template.encode(&Nest::Bytes(&[1; 256])));
+        });
+        create_template_then(TemplateId::DynamicAlpha, |template| {
+            let mut written = vec![];
+            assert_eq!(
+                template.encode_to_writer(&Nest::Bytes(&[1; 256]), &mut written),
+                Err(Error::dynamic__encode_to__bytes_length_should_be_lte_max_length)
+            );
+        });
+    }
+
+    #[test]
+    fn should_error__write_to__io_error() {
+        create_template_then(TemplateId::DynamicAlpha, |template| {
+            let mut buffer = [0; 4];
+            assert_eq!(
+                template.encode_to_writer(&Nest::Bytes(&[1; 8]), &mut &mut buffer[..]),
+                Err(Error::encoder__write_to__io_error(ErrorKind::WriteZero))
+            );
+        });
+    }
+
+    struct ByteAtATime<'a>(&'a [u8]);
+
+    impl<'a> Read for ByteAtATime<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn should_decode_from_reader_with_short_reads() {
+        create_template_then(TemplateId::DynamicBeta, |template| {
+            let mut reader = BufReader::with_capacity(1, ByteAtATime(&[3, 0, 1, 2, 3, 0, 0]));
+            assert_eq!(template.decode_from_reader(&mut reader), Ok(Some(Nest::Bytes(&[1, 2, 3]))));
+            assert_eq!(template.decode_from_reader(&mut reader), Ok(Some(Nest::Bytes(&[]))));
+            assert_eq!(template.decode_from_reader(&mut reader), Ok(None));
+        });
+    }
+
+    #[test]
+    fn should_error__read_from__unexpected_eof() {
+        create_template_then(TemplateId::DynamicBeta, |template| {
+            assert_eq!(
+                template.decode_from_reader(&mut &[3][..]),
+                Err(Error::encoder__read_from__unexpected_eof)
+            );
+        });
+        create_template_then(TemplateId::DynamicBeta, |template| {
+            assert_eq!(
+                template.decode_from_reader(&mut &[3, 0, 1, 2][..]),
+                Err(Error::encoder__read_from__unexpected_eof)
+            );
+        });
+    }
+
+    #[test]
+    fn should_validate() {
+        create_template_then(TemplateId::DynamicBeta, |template| {
+            assert_eq!(template.validate(&[0, 0]), Ok(2));
+            assert_eq!(template.validate(&[2, 0, 1, 2, 3]), Ok(4));
+            assert_eq!(
+                template.validate(&[2]),
+                Err(Error::dynamic__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length)
+            );
+            assert_eq!(
+                template.validate(&[2, 0, 1]),
+                Err(Error::dynamic__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length_plus_length)
+            );
+        });
+    }
+
+    #[test]
+    fn should_skip() {
+        create_template_then(TemplateId::DynamicAlpha, |template| {
+            assert_eq!(template.skip(&[2, 1, 2, 3, 4]), Ok(3));
+            assert_eq!(
+                template.skip(&[2, 1]),
+                Err(Error::dynamic__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length_plus_length)
+            );
+        });
+    }
+
     #[test]
     fn should_jinyang() {
         let templateAlpha = Template::from_jinyang(&[2]).unwrap();
@@ -561,4 +758,27 @@ mod tests {
         assert_eq!(templateDelta.export_jinyang(), vec![5]);
     }
 
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn should_round_trip_random_nests() {
+        use arbitrary::Generator;
+        use rand::SeedableRng;
+        use rand::rngs::StdRng;
+        let mut rng = StdRng::seed_from_u64(0);
+        let generator = Generator { max_bytes: 70000, ..Generator::default() };
+        for template_id in [
+            TemplateId::DynamicAlpha,
+            TemplateId::DynamicBeta,
+            TemplateId::DynamicGamma,
+            TemplateId::DynamicDelta
+        ] {
+            let template = Template::new(Box::new(Dynamic::new(template_id).unwrap()));
+            for _ in 0..20 {
+                let nest = generator.nest(&template, &mut rng);
+                let encoding = template.encode(&nest).unwrap();
+                assert_eq!(template.decode(&encoding), Ok(nest));
+            }
+        }
+    }
+
 }
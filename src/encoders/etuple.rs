@@ -0,0 +1,433 @@
+use error::Error;
+use traits::encoder::Encoder;
+use template_ids::TemplateId;
+use encoders::tuple::{
+    Field,
+    check_fields,
+    fields_from_jinyang_with_remainder,
+    metadata_from_jinyang_with_remainder,
+    export_metadata_to
+};
+use std::io::{Read, Write};
+use std::any::Any;
+use nest::Nest;
+use size_bounds::SizeBounds;
+use patch::LengthField;
+use length_prefix;
+use template_kind::TemplateKind;
+
+/// An extensible tuple: the fields are prefixed with their total length, so
+/// later versions can append fields that older decoders skip.
+///
+/// Decoding keeps trailing bytes it has no field for as one extra `Bytes`
+/// child, and encoding writes such a child back after the fields, so values
+/// survive a round trip through an older template. Fields missing from the
+/// end of an encoding decode as their default; only trailing fields can
+/// have one.
+///
+/// The jinyang is the Tuple's, with each field's default between the field
+/// templates and the metadata: 0 for none, or 1 and the default encoded with
+/// the field's template.
+pub struct Etuple {
+    template_id: TemplateId,
+    length_encoding_length: usize,
+    max_length: usize,
+    fields: Vec<Field>
+}
+
+impl Etuple {
+    pub fn new(template_id: TemplateId, fields: Vec<Field>) -> Result<Etuple, Error> {
+        let (length_encoding_length, max_length) = match template_id {
+            TemplateId::EtupleAlpha => (1, 255),
+            TemplateId::EtupleBeta => (2, 65535),
+            TemplateId::EtupleGamma => (3, 16777215),
+            TemplateId::EtupleDelta => (4, 4294967295),
+            _ => return Err(Error::etuple__new__invalid_template_id)
+        };
+        check_fields(&fields)?;
+        let first_default = fields.iter().position(|field| field.default.is_some()).unwrap_or(fields.len());
+        for field in &fields[first_default..] {
+            match field.default {
+                Some(ref default) => {
+                    field.template.encode(default)?;
+                },
+                None => return Err(Error::etuple__new__defaults_should_be_trailing)
+            }
+        }
+        Ok(Etuple {
+            template_id,
+            length_encoding_length,
+            max_length,
+            fields
+        })
+    }
+    pub fn from_jinyang_with_remainder(template_id: TemplateId, jinyang: &[u8], depth: usize) -> Result<(Etuple, &[u8]), Error> {
+        let (mut fields, mut remainder) = fields_from_jinyang_with_remainder(jinyang, depth)?;
+        for field in fields.iter_mut() {
+            match remainder.first() {
+                None => return Err(Error::tuple__from_jinyang__unexpected_end),
+                Some(0) => remainder = &remainder[1..],
+                Some(1) => {
+                    let (default, next_remainder) = field.template.decode_with_remainder(&remainder[1..])?;
+                    field.default = Some(default.to_owned_nest());
+                    remainder = next_remainder;
+                },
+                Some(_) => return Err(Error::etuple__from_jinyang__invalid_default_flag)
+            }
+        }
+        let remainder = metadata_from_jinyang_with_remainder(&mut fields, remainder)?;
+        Ok((Etuple::new(template_id, fields)?, remainder))
+    }
+    pub fn length_encoding_length(&self) -> usize {
+        self.length_encoding_length
+    }
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+    /// `nest` without the unknown trailing data an older template kept.
+    pub fn without_unknown<'a>(&self, nest: &Nest<'a>) -> Nest<'a> {
+        Nest::OwnedNests(nest.iter_nests().take(self.fields.len()).cloned().collect())
+    }
+    pub fn encode_length_to(&self, length: usize, to: &mut Vec<u8>) {
+        length_prefix::encode_length_to(length, self.length_encoding_length, to);
+    }
+    pub fn decode_length(&self, bytes: &[u8]) -> Result<usize, Error> {
+        length_prefix::decode_length(
+            bytes,
+            self.length_encoding_length,
+            Error::etuple__split_body__bytes_length_should_be_gte_length_encoding_length
+        )
+    }
+    fn export_defaults_to(&self, to: &mut Vec<u8>) {
+        for field in &self.fields {
+            match field.default {
+                Some(ref default) => {
+                    to.push(1);
+                    field.template.encoder().encode_to(default, to).unwrap();
+                },
+                None => to.push(0)
+            }
+        }
+    }
+    /// Splits off the length prefix and returns the fields' bytes and the
+    /// bytes after them.
+    fn split_body<'a>(&self, bytes: &'a [u8]) -> Result<(&'a [u8], &'a [u8]), Error> {
+        let length = self.decode_length(bytes)?;
+        if bytes.len() - self.length_encoding_length < length {
+            Err(Error::etuple__decode_with_remainder__bytes_length_should_be_gte_length)
+        } else {
+            Ok(bytes[self.length_encoding_length..].split_at(length))
+        }
+    }
+    fn decode_body<'a>(&self, body: &'a [u8]) -> Result<Nest<'a>, Error> {
+        let mut nests = Vec::with_capacity(self.fields.len() + 1);
+        let mut remainder = body;
+        for field in &self.fields {
+            if remainder.is_empty() {
+                let default = field.default.as_ref()
+                    .ok_or(Error::etuple__decode_with_remainder__missing_field_should_have_default)?;
+                nests.push(default.clone());
+            } else {
+                let (nest, next_remainder) = field.template.decode_with_remainder(remainder)?;
+                nests.push(nest);
+                remainder = next_remainder;
+            }
+        }
+        if !remainder.is_empty() {
+            nests.push(Nest::Bytes(remainder));
+        }
+        Ok(Nest::OwnedNests(nests))
+    }
+    /// The fields of `nest` and the unknown data after them, if any.
+    fn split_nests<'b, 'a>(&self, nest: &'b Nest<'a>) -> Result<(Vec<&'b Nest<'a>>, &'b [u8]), Error> {
+        let mut nests = nest.try_nests()?.collect::<Vec<_>>();
+        if nests.len() == self.fields.len() + 1 {
+            let unknown = nests.pop().unwrap();
+            Ok((nests, unknown.try_bytes()?))
+        } else if nests.len() == self.fields.len() {
+            Ok((nests, &[]))
+        } else {
+            Err(Error::etuple__encode_to__nests_length_should_match_fields_length)
+        }
+    }
+    fn body_length(&self, nests: &[&Nest], unknown: &[u8]) -> Result<usize, Error> {
+        let mut length = unknown.len();
+        for (field, nest) in self.fields.iter().zip(nests) {
+            length += field.template.encoder().encoded_len(nest)?;
+        }
+        if length > self.max_length {
+            Err(Error::etuple__encode_to__body_length_should_be_lte_max_length)
+        } else {
+            Ok(length)
+        }
+    }
+}
+
+impl Encoder for Etuple {
+    fn template_id(&self) -> u8 {
+        self.template_id as u8
+    }
+    fn kind(&self) -> TemplateKind<'_> {
+        TemplateKind::Etuple {
+            length_encoding_length: self.length_encoding_length,
+            max_length: self.max_length,
+            fields: &self.fields
+        }
+    }
+    fn encode_to(&self, nest: &Nest, to: &mut Vec<u8>) -> Result<(), Error> {
+        let (nests, unknown) = self.split_nests(nest)?;
+        self.encode_length_to(self.body_length(&nests, unknown)?, to);
+        for (field, nest) in self.fields.iter().zip(nests) {
+            field.template.encoder().encode_to(nest, to)?;
+        }
+        to.extend_from_slice(unknown);
+        Ok(())
+    }
+    fn write_to(&self, nest: &Nest, to: &mut dyn Write) -> Result<(), Error> {
+        let (nests, unknown) = self.split_nests(nest)?;
+        let mut length_encoding = Vec::with_capacity(self.length_encoding_length);
+        self.encode_length_to(self.body_length(&nests, unknown)?, &mut length_encoding);
+        to.write_all(&length_encoding).map_err(|error| Error::encoder__write_to__io_error(error.kind()))?;
+        for (field, nest) in self.fields.iter().zip(nests) {
+            field.template.encoder().write_to(nest, to)?;
+        }
+        to.write_all(unknown).map_err(|error| Error::encoder__write_to__io_error(error.kind()))
+    }
+    fn encoded_len(&self, nest: &Nest) -> Result<usize, Error> {
+        let (nests, unknown) = self.split_nests(nest)?;
+        Ok(self.length_encoding_length + self.body_length(&nests, unknown)?)
+    }
+    fn size_bounds(&self) -> SizeBounds {
+        let min = self.fields.iter()
+            .filter(|field| field.default.is_none())
+            .map(|field| field.template.size_bounds().min)
+            .sum::<usize>();
+        SizeBounds {
+            min: self.length_encoding_length + min,
+            max: self.length_encoding_length.checked_add(self.max_length),
+            payload_offset: Some(self.length_encoding_length)
+        }
+    }
+    fn default_nest(&self) -> Nest<'static> {
+        Nest::OwnedNests(self.fields.iter().map(|field| {
+            field.default.clone().unwrap_or_else(|| field.template.default_nest())
+        }).collect())
+    }
+    fn read_from(&self, from: &mut dyn Read) -> Result<Nest<'static>, Error> {
+        let length = length_prefix::read_length_from(from, self.length_encoding_length)?;
+        let mut body = Vec::new();
+        (&mut *from).take(length as u64).read_to_end(&mut body).map_err(Error::from_read_error)?;
+        if body.len() < length {
+            return Err(Error::encoder__read_from__unexpected_eof);
+        }
+        Ok(self.decode_body(&body)?.to_owned_nest())
+    }
+    fn decode_with_remainder<'a>(&self, bytes: &'a [u8]) -> Result<(Nest<'a>, &'a [u8]), Error> {
+        let (body, remainder) = self.split_body(bytes)?;
+        Ok((self.decode_body(body)?, remainder))
+    }
+    fn validate(&self, bytes: &[u8]) -> Result<usize, Error> {
+        let (body, _) = self.split_body(bytes)?;
+        let mut consumed = 0;
+        for field in &self.fields {
+            if consumed == body.len() {
+                if field.default.is_none() {
+                    return Err(Error::etuple__decode_with_remainder__missing_field_should_have_default);
+                }
+            } else {
+                consumed += field.template.encoder().validate(&body[consumed..])?;
+            }
+        }
+        Ok(self.length_encoding_length + body.len())
+    }
+    fn skip(&self, bytes: &[u8]) -> Result<usize, Error> {
+        let (body, _) = self.split_body(bytes)?;
+        Ok(self.length_encoding_length + body.len())
+    }
+    fn locate(&self, encoding: &[u8], start: usize, path: &[usize], length_fields: &mut Vec<LengthField>) -> Result<(&dyn Encoder, usize), Error> {
+        if start > encoding.len() {
+            return Err(Error::encoder__locate__start_should_be_lte_encoding_length);
+        }
+        if path.is_empty() {
+            return Ok((self, start));
+        }
+        if path[0] >= self.fields.len() {
+            return Err(Error::encoder__locate__index_should_be_lt_length);
+        }
+        let (body, _) = self.split_body(&encoding[start..])?;
+        let body_start = start + self.length_encoding_length;
+        let mut field_start = body_start;
+        for field in &self.fields[..path[0]] {
+            if field_start == body_start + body.len() {
+                break;
+            }
+            field_start += field.template.encoder().skip(&encoding[field_start..body_start + body.len()])?;
+        }
+        if field_start == body_start + body.len() {
+            return Err(Error::etuple__locate__field_should_be_present);
+        }
+        length_fields.push(LengthField {
+            position: start,
+            length_encoding_length: self.length_encoding_length,
+            max_length: self.max_length
+        });
+        self.fields[path[0]].template.encoder().locate(encoding, field_start, &path[1..], length_fields)
+    }
+    fn export_jinyang_to(&self, to: &mut Vec<u8>) {
+        to.push(self.fields.len() as u8);
+        for field in &self.fields {
+            field.template.export_jinyang_to(to);
+        }
+        self.export_defaults_to(to);
+        export_metadata_to(&self.fields, to);
+    }
+    fn export_canonical_jinyang_to(&self, to: &mut Vec<u8>) {
+        to.push(self.fields.len() as u8);
+        for field in &self.fields {
+            field.template.export_canonical_jinyang_to(to);
+        }
+        self.export_defaults_to(to);
+        to.push(0);
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template_ids::TemplateId;
+    use template::Template;
+    use encoders::etuple::Etuple;
+    use encoders::tuple::Field;
+    use error::Error;
+    use nest::Nest;
+
+    fn v1() -> Template {
+        Template::from_schema("etuple<=255(id:fixed(1),body:dynamic<=255)").unwrap()
+    }
+
+    fn v2() -> Template {
+        Template::from_schema("etuple<=255(id:fixed(1),body:dynamic<=255,flags:fixed(1)=0x07)").unwrap()
+    }
+
+    #[test]
+    fn should_encode_decode() {
+        let nest = Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[2, 3])]);
+        let encoding = v1().encode(&nest).unwrap();
+        assert_eq!(encoding, vec![4, 1, 2, 2, 3]);
+        assert_eq!(v1().decode(&encoding), Ok(nest));
+        assert_eq!(v1().validate(&[4, 1, 2, 2, 3, 9]), Ok(5));
+        assert_eq!(v1().skip(&[4, 1, 2, 2, 3, 9]), Ok(5));
+        assert_eq!(
+            v1().decode(&[4, 1, 2, 2]).err(),
+            Some(Error::etuple__decode_with_remainder__bytes_length_should_be_gte_length)
+        );
+        assert_eq!(
+            v1().decode(&[]).err(),
+            Some(Error::etuple__split_body__bytes_length_should_be_gte_length_encoding_length)
+        );
+        assert_eq!(
+            v1().encode(&Nest::Nests(&[&Nest::Bytes(&[1])])).err(),
+            Some(Error::etuple__encode_to__nests_length_should_match_fields_length)
+        );
+    }
+
+    #[test]
+    fn should_keep_unknown_trailing_fields() {
+        let encoding = v2().encode(&Nest::Nests(&[
+            &Nest::Bytes(&[1]),
+            &Nest::Bytes(&[2]),
+            &Nest::Bytes(&[3])
+        ])).unwrap();
+        assert_eq!(encoding, vec![4, 1, 1, 2, 3]);
+        let old = v1().decode(&encoding).unwrap();
+        assert_eq!(old, Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[2]), &Nest::Bytes(&[3])]));
+        assert_eq!(v1().encode(&old), Ok(encoding.clone()));
+        let v1 = v1();
+        let etuple = v1.encoder().as_any().downcast_ref::<Etuple>().unwrap();
+        assert_eq!(etuple.without_unknown(&old), Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[2])]));
+    }
+
+    #[test]
+    fn should_fill_in_defaults() {
+        assert_eq!(
+            v2().decode(&[3, 1, 1, 2]),
+            Ok(Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[2]), &Nest::Bytes(&[7])]))
+        );
+        assert_eq!(v2().validate(&[3, 1, 1, 2]), Ok(4));
+        assert_eq!(
+            v2().decode(&[1, 1]).err(),
+            Some(Error::etuple__decode_with_remainder__missing_field_should_have_default)
+        );
+        assert_eq!(
+            v2().validate(&[1, 1]).err(),
+            Some(Error::etuple__decode_with_remainder__missing_field_should_have_default)
+        );
+        assert_eq!(v2().size_bounds().min, 3);
+    }
+
+    #[test]
+    fn should_read_from_stream() {
+        let mut reader: &[u8] = &[3, 1, 1, 2, 4, 1, 1, 2, 3];
+        assert_eq!(
+            v2().decode_from_reader(&mut reader),
+            Ok(Some(Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[2]), &Nest::Bytes(&[7])])))
+        );
+        assert_eq!(
+            v1().decode_from_reader(&mut reader),
+            Ok(Some(Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[2]), &Nest::Bytes(&[3])])))
+        );
+    }
+
+    #[test]
+    fn should_patch_fields() {
+        let mut encoding = vec![4, 1, 1, 2, 3];
+        assert_eq!(v1().patch(&mut encoding, &[1], &Nest::Bytes(&[5, 6])), Ok(()));
+        assert_eq!(encoding, vec![5, 1, 2, 5, 6, 3]);
+        assert_eq!(
+            v2().patch(&mut vec![3, 1, 1, 2], &[2], &Nest::Bytes(&[1])).err(),
+            Some(Error::etuple__locate__field_should_be_present)
+        );
+    }
+
+    #[test]
+    fn should_jinyang() {
+        let jinyang = v2().export_jinyang();
+        assert_eq!(&jinyang[..11], &[15, 3, 0, 0, 2, 0, 0, 0, 0, 1, 7]);
+        assert_eq!(Template::from_jinyang(&jinyang).unwrap().export_jinyang(), jinyang);
+        assert_eq!(v2().export_canonical_jinyang(), vec![15, 3, 0, 0, 2, 0, 0, 0, 0, 1, 7, 0]);
+        assert_ne!(v1().fingerprint(), v2().fingerprint());
+    }
+
+    #[test]
+    fn should_error_when_invalid() {
+        let fixed = || Template::from_schema("fixed(1)").unwrap();
+        assert_eq!(
+            Etuple::new(TemplateId::Tuple, vec![Field::new(fixed())]).err(),
+            Some(Error::etuple__new__invalid_template_id)
+        );
+        assert_eq!(
+            Etuple::new(TemplateId::EtupleAlpha, vec![
+                Field::new(fixed()).with_default(Nest::OwnedBytes(vec![0])),
+                Field::new(fixed())
+            ]).err(),
+            Some(Error::etuple__new__defaults_should_be_trailing)
+        );
+        assert_eq!(
+            Etuple::new(TemplateId::EtupleAlpha, vec![
+                Field::new(fixed()).with_default(Nest::OwnedBytes(vec![0, 0]))
+            ]).err(),
+            Some(Error::fixed__encode_to__bytes_length_should_match_self_length)
+        );
+        assert_eq!(
+            Template::from_jinyang_with_remainder(&[15, 1, 0, 0, 2, 0]).err(),
+            Some(Error::etuple__from_jinyang__invalid_default_flag)
+        );
+    }
+
+}
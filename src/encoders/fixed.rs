@@ -2,9 +2,12 @@ use error::Error;
 use traits::encoder::Encoder;
 use template_ids::TemplateId;
 use byteorder::{LittleEndian, WriteBytesExt, ReadBytesExt};
-use std::io::Cursor;
+use std::io::{Cursor, Read, Write};
 use std::any::Any;
 use nest::Nest;
+use size_bounds::SizeBounds;
+use patch::LengthField;
+use template_kind::TemplateKind;
 
 pub struct Fixed {
     template_id: TemplateId,
@@ -44,6 +47,9 @@ impl Fixed  {
     pub fn from_jinyang_with_remainder(template_id: TemplateId, jinyang: &[u8]) -> Result<(Fixed, &[u8]), Error> {
         match(template_id) {
             TemplateId::FixedAlpha => {
+                if jinyang.is_empty() {
+                    return Err(Error::fixed__from_jinyang__unexpected_end);
+                }
                 Ok((
                     Fixed::new(
                         template_id,
@@ -53,6 +59,9 @@ impl Fixed  {
                 ))
             },
             TemplateId::FixedBeta => {
+                if jinyang.len() < 2 {
+                    return Err(Error::fixed__from_jinyang__unexpected_end);
+                }
                 Ok((
                     Fixed::new(
                         template_id,
@@ -76,8 +85,13 @@ impl Encoder for Fixed {
     fn template_id(&self) -> u8 {
         self.template_id as u8
     }
+    fn kind(&self) -> TemplateKind<'_> {
+        TemplateKind::Fixed {
+            length: self.length
+        }
+    }
     fn encode_to<'a>(&self, nest: &Nest, to: &'a mut Vec<u8>) -> Result<(), Error> {
-        let bytes = nest.bytes();
+        let bytes = nest.try_bytes()?;
         if bytes.len() != self.length {
             Err(Error::fixed__encode_to__bytes_length_should_match_self_length)
         } else {
@@ -85,6 +99,36 @@ impl Encoder for Fixed {
             Ok(())
         }
     }
+    fn write_to(&self, nest: &Nest, to: &mut dyn Write) -> Result<(), Error> {
+        let bytes = nest.try_bytes()?;
+        if bytes.len() != self.length {
+            Err(Error::fixed__encode_to__bytes_length_should_match_self_length)
+        } else {
+            to.write_all(bytes).map_err(|error| Error::encoder__write_to__io_error(error.kind()))
+        }
+    }
+    fn encoded_len(&self, nest: &Nest) -> Result<usize, Error> {
+        if nest.try_bytes()?.len() != self.length {
+            Err(Error::fixed__encode_to__bytes_length_should_match_self_length)
+        } else {
+            Ok(self.length)
+        }
+    }
+    fn size_bounds(&self) -> SizeBounds {
+        SizeBounds {
+            min: self.length,
+            max: Some(self.length),
+            payload_offset: Some(0)
+        }
+    }
+    fn default_nest(&self) -> Nest<'static> {
+        Nest::OwnedBytes(vec![0; self.length])
+    }
+    fn read_from(&self, from: &mut dyn Read) -> Result<Nest<'static>, Error> {
+        let mut bytes = vec![0; self.length];
+        from.read_exact(&mut bytes).map_err(Error::from_read_error)?;
+        Ok(Nest::OwnedBytes(bytes))
+    }
     fn decode_with_remainder<'a>(&self, bytes: &'a [u8]) -> Result<(Nest<'a>, &'a [u8]), Error> {
         if bytes.len() < self.length {
             Err(Error::fixed__decode_with_remainder__bytes_length_should_be_gte_self_length)
@@ -95,6 +139,26 @@ impl Encoder for Fixed {
             ))
         }
     }
+    fn validate(&self, bytes: &[u8]) -> Result<usize, Error> {
+        if bytes.len() < self.length {
+            Err(Error::fixed__decode_with_remainder__bytes_length_should_be_gte_self_length)
+        } else {
+            Ok(self.length)
+        }
+    }
+    fn skip(&self, bytes: &[u8]) -> Result<usize, Error> {
+        self.validate(bytes)
+    }
+    fn locate(&self, encoding: &[u8], start: usize, path: &[usize], _length_fields: &mut Vec<LengthField>) -> Result<(&dyn Encoder, usize), Error> {
+        if start > encoding.len() {
+            return Err(Error::encoder__locate__start_should_be_lte_encoding_length);
+        }
+        if path.is_empty() {
+            Ok((self, start))
+        } else {
+            Err(Error::encoder__locate__path_should_end_at_bytes)
+        }
+    }
     fn export_jinyang_to<'a>(&self, to: &'a mut Vec<u8>) {
         match(self.template_id) {
             TemplateId::FixedAlpha => {
@@ -309,6 +373,62 @@ mod fixed {
         });
     }
 
+    #[test]
+    fn should_encode_to_writer() {
+        create_fixed_template_then(TemplateId::FixedAlpha, 4, |template| {
+            let mut written = vec![];
+            assert_eq!(template.encode_to_writer(&Nest::Bytes(&[1, 2, 3, 4]), &mut written), Ok(()));
+            assert_eq!(written, vec![1, 2, 3, 4]);
+        });
+        create_fixed_template_then(TemplateId::FixedBeta, 257, |template| {
+            let mut written = vec![];
+            assert_eq!(
+                template.encode_to_writer(&Nest::Bytes(&[1; 256]), &mut written),
+                Err(Error::fixed__encode_to__bytes_length_should_match_self_length)
+            );
+            assert_eq!(written, Vec::<u8>::new());
+        });
+    }
+
+    #[test]
+    fn should_measure_encoded_len() {
+        create_fixed_template_then(TemplateId::FixedBeta, 300, |template| {
+            assert_eq!(template.encoded_len(&Nest::Bytes(&[1; 300])), Ok(300));
+            assert_eq!(
+                template.encoded_len(&Nest::Bytes(&[1; 299])),
+                Err(Error::fixed__encode_to__bytes_length_should_match_self_length)
+            );
+        });
+    }
+
+    #[test]
+    fn should_decode_from_reader() {
+        create_fixed_template_then(TemplateId::FixedAlpha, 2, |template| {
+            let mut reader = &[1, 2, 3, 4][..];
+            assert_eq!(template.decode_from_reader(&mut reader), Ok(Some(Nest::Bytes(&[1, 2]))));
+            assert_eq!(template.decode_from_reader(&mut reader), Ok(Some(Nest::Bytes(&[3, 4]))));
+            assert_eq!(template.decode_from_reader(&mut reader), Ok(None));
+        });
+        create_fixed_template_then(TemplateId::FixedAlpha, 2, |template| {
+            assert_eq!(
+                template.decode_from_reader(&mut &[1][..]),
+                Err(Error::encoder__read_from__unexpected_eof)
+            );
+        });
+    }
+
+    #[test]
+    fn should_validate() {
+        create_fixed_template_then(TemplateId::FixedAlpha, 2, |template| {
+            assert_eq!(template.validate(&[1, 2]), Ok(2));
+            assert_eq!(template.validate(&[1, 2, 3]), Ok(2));
+            assert_eq!(
+                template.validate(&[1]),
+                Err(Error::fixed__decode_with_remainder__bytes_length_should_be_gte_self_length)
+            );
+        });
+    }
+
     #[test]
     fn should_error_when_decode_not_enough_bytes() {
         create_fixed_template_then(TemplateId::FixedAlpha, 1, |template| {
@@ -346,6 +466,26 @@ mod fixed {
         assert_eq!(template65792.export_jinyang(), vec![1, 255, 255]);
     }
 
-
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn should_round_trip_random_nests() {
+        use arbitrary::random_nest;
+        use rand::{Rng, SeedableRng};
+        use rand::rngs::StdRng;
+        let mut rng = StdRng::seed_from_u64(0);
+        for length in [1, 2, 255, 256, 257, 258, 65791, 65792] {
+            let template_id = if length > 256 { TemplateId::FixedBeta } else { TemplateId::FixedAlpha };
+            let template = Template::new(Box::new(Fixed::new(template_id, length).unwrap()));
+            let nest = random_nest(&template, &mut rng);
+            assert_eq!(template.decode(&template.encode(&nest).unwrap()), Ok(nest));
+        }
+        for _ in 0..100 {
+            let length = rng.gen_range(1..=65792);
+            let template_id = if length > 256 { TemplateId::FixedBeta } else { TemplateId::FixedAlpha };
+            let template = Template::from_jinyang(&Template::new(Box::new(Fixed::new(template_id, length).unwrap())).export_jinyang()).unwrap();
+            let nest = random_nest(&template, &mut rng);
+            assert_eq!(template.decode(&template.encode(&nest).unwrap()), Ok(nest));
+        }
+    }
 
 }
@@ -0,0 +1,423 @@
+use error::Error;
+use traits::encoder::Encoder;
+use template_ids::TemplateId;
+use template::Template;
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{Cursor, Read, Write};
+use std::any::Any;
+use nest::Nest;
+use size_bounds::SizeBounds;
+use patch::LengthField;
+use length_prefix;
+use template_kind::TemplateKind;
+
+/// Offsets take four bytes whatever the tier, so the tier only limits the
+/// number of nests, not how long they are.
+pub const OFFSET_ENCODING_LENGTH: usize = 4;
+/// The most bytes the nests of any Ilist can add up to.
+pub const MAX_NESTS_LENGTH: usize = 4294967295;
+
+/// A list whose encoding is the length, then one offset per nest, then the
+/// nests. Each offset is the end of its nest relative to the start of the
+/// nests, so any nest can be found without decoding the ones before it.
+/// Offsets are `OFFSET_ENCODING_LENGTH` bytes long.
+pub struct Ilist {
+    template_id: TemplateId,
+    length_encoding_length: usize,
+    max_length: usize,
+    template: Template
+}
+
+impl Ilist  {
+    pub fn new(template_id: TemplateId, template: Template) -> Result<Ilist, Error> {
+        match template_id {
+            TemplateId::IlistAlpha => {
+                Ok(Ilist{
+                    template_id,
+                    length_encoding_length: 1,
+                    max_length: 255,
+                    template
+                })
+            },
+            TemplateId::IlistBeta => {
+                Ok(Ilist{
+                    template_id,
+                    length_encoding_length: 2,
+                    max_length: 65535,
+                    template
+                })
+            },
+            TemplateId::IlistGamma => {
+                Ok(Ilist{
+                    template_id,
+                    length_encoding_length: 3,
+                    max_length: 16777215,
+                    template
+                })
+            },
+            TemplateId::IlistDelta => {
+                Ok(Ilist{
+                    template_id,
+                    length_encoding_length: 4,
+                    max_length: 4294967295,
+                    template
+                })
+            },
+            _ => {
+                Err(Error::ilist__new__invalid_template_id)
+            }
+        }
+    }
+    pub fn from_jinyang_with_remainder(template_id: TemplateId, jinyang: &[u8], depth: usize) -> Result<(Ilist, &[u8]), Error> {
+        let (template, remainder) = Template::from_jinyang_at_depth(jinyang, depth + 1)?;
+        Ok((Ilist::new(template_id, template)?, remainder))
+    }
+    pub fn length_encoding_length(&self) -> usize {
+        self.length_encoding_length
+    }
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+    pub fn template(&self) -> &Template {
+        &self.template
+    }
+    pub fn encode_length_to(&self, length: usize, to: &mut Vec<u8>) {
+        length_prefix::encode_length_to(length, self.length_encoding_length, to);
+    }
+    pub fn decode_length(&self, bytes: &[u8]) -> Result<usize, Error> {
+        length_prefix::decode_length(
+            bytes,
+            self.length_encoding_length,
+            Error::ilist__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length
+        )
+    }
+    pub fn encode_offset_to(&self, offset: usize, to: &mut Vec<u8>) {
+        length_prefix::encode_length_to(offset, OFFSET_ENCODING_LENGTH, to);
+    }
+    /// Encodes the length and offsets of `nest`, everything before the nests themselves.
+    fn encode_header_to(&self, nest: &Nest, to: &mut Vec<u8>) -> Result<(), Error> {
+        let nests = nest.try_nests()?;
+        if nests.len() > self.max_length {
+            return Err(Error::ilist__encode_to__nests_length_should_be_lte_max_length);
+        }
+        self.encode_length_to(nests.len(), to);
+        let mut offset: usize = 0;
+        for nest in nests {
+            offset += self.template.encoder().encoded_len(nest)?;
+            if offset > MAX_NESTS_LENGTH {
+                return Err(Error::ilist__encode_to__nests_encoding_length_should_be_lte_max_length);
+            }
+            self.encode_offset_to(offset, to);
+        }
+        Ok(())
+    }
+    /// Splits an encoding into the length, the offsets and the bytes after the
+    /// offsets, checking that the offsets are ascending and in bounds.
+    pub fn decode_header<'a>(&self, bytes: &'a [u8]) -> Result<(usize, &'a [u8], &'a [u8]), Error> {
+        let length = self.decode_length(bytes)?;
+        let remainder = &bytes[self.length_encoding_length..];
+        let offsets_length = match length.checked_mul(OFFSET_ENCODING_LENGTH) {
+            Some(offsets_length) if offsets_length <= remainder.len() => offsets_length,
+            _ => return Err(Error::ilist__decode_with_remainder__bytes_length_should_be_gte_offsets_length)
+        };
+        let (offsets, remainder) = remainder.split_at(offsets_length);
+        let mut previous_offset = 0;
+        for index in 0..length {
+            let offset = self.offset_at(offsets, index);
+            if offset < previous_offset {
+                return Err(Error::ilist__decode_with_remainder__offsets_should_be_ascending);
+            }
+            previous_offset = offset;
+        }
+        if previous_offset > remainder.len() {
+            return Err(Error::ilist__decode_with_remainder__offsets_should_be_lte_bytes_length);
+        }
+        Ok((length, offsets, remainder))
+    }
+    fn offset_at(&self, offsets: &[u8], index: usize) -> usize {
+        let start = index * OFFSET_ENCODING_LENGTH;
+        Cursor::new(&offsets[start..start + OFFSET_ENCODING_LENGTH]).read_u32::<LittleEndian>().unwrap() as usize
+    }
+    pub fn range_at(&self, offsets: &[u8], index: usize) -> (usize, usize) {
+        let start = if index == 0 { 0 } else { self.offset_at(offsets, index - 1) };
+        (start, self.offset_at(offsets, index))
+    }
+    /// Decodes the nest at `index` straight from an encoding of the whole list.
+    pub fn get<'a>(&self, bytes: &'a [u8], index: usize) -> Result<Nest<'a>, Error> {
+        let (length, offsets, nests_bytes) = self.decode_header(bytes)?;
+        if index >= length {
+            return Err(Error::ilist__get__index_should_be_lt_length);
+        }
+        let (start, end) = self.range_at(offsets, index);
+        self.decode_exactly(&nests_bytes[start..end])
+    }
+    fn decode_exactly<'a>(&self, bytes: &'a [u8]) -> Result<Nest<'a>, Error> {
+        let (nest, remainder) = self.template.encoder().decode_with_remainder(bytes)?;
+        if !remainder.is_empty() {
+            Err(Error::ilist__decode_with_remainder__nest_length_should_match_offsets)
+        } else {
+            Ok(nest)
+        }
+    }
+}
+
+impl Encoder for Ilist {
+    fn template_id(&self) -> u8 {
+        self.template_id as u8
+    }
+    fn kind(&self) -> TemplateKind<'_> {
+        TemplateKind::Ilist {
+            length_encoding_length: self.length_encoding_length,
+            max_length: self.max_length,
+            template: &self.template
+        }
+    }
+    fn encode_to(&self, nest: &Nest, to: &mut Vec<u8>) -> Result<(), Error> {
+        self.encode_header_to(nest, to)?;
+        for nest in nest.try_nests()? {
+            self.template.encoder().encode_to(nest, to)?;
+        }
+        Ok(())
+    }
+    fn write_to(&self, nest: &Nest, to: &mut dyn Write) -> Result<(), Error> {
+        let mut header = Vec::new();
+        self.encode_header_to(nest, &mut header)?;
+        to.write_all(&header).map_err(|error| Error::encoder__write_to__io_error(error.kind()))?;
+        for nest in nest.try_nests()? {
+            self.template.encoder().write_to(nest, to)?;
+        }
+        Ok(())
+    }
+    fn encoded_len(&self, nest: &Nest) -> Result<usize, Error> {
+        let nests = nest.try_nests()?;
+        let length = nests.len();
+        if length > self.max_length {
+            return Err(Error::ilist__encode_to__nests_length_should_be_lte_max_length);
+        }
+        let mut nests_length = 0;
+        for nest in nests {
+            nests_length += self.template.encoder().encoded_len(nest)?;
+        }
+        if nests_length > MAX_NESTS_LENGTH {
+            return Err(Error::ilist__encode_to__nests_encoding_length_should_be_lte_max_length);
+        }
+        Ok(self.length_encoding_length + OFFSET_ENCODING_LENGTH * length + nests_length)
+    }
+    fn size_bounds(&self) -> SizeBounds {
+        let nests_max = self.template.encoder().size_bounds().max
+            .and_then(|max| max.checked_mul(self.max_length))
+            .map_or(MAX_NESTS_LENGTH, |max| max.min(MAX_NESTS_LENGTH));
+        let max = self.max_length.checked_mul(OFFSET_ENCODING_LENGTH)
+            .and_then(|offsets_max| offsets_max.checked_add(self.length_encoding_length))
+            .and_then(|header_max| header_max.checked_add(nests_max));
+        SizeBounds {
+            min: self.length_encoding_length,
+            max,
+            payload_offset: None
+        }
+    }
+    fn default_nest(&self) -> Nest<'static> {
+        Nest::OwnedNests(vec![])
+    }
+    fn read_from(&self, from: &mut dyn Read) -> Result<Nest<'static>, Error> {
+        let length = length_prefix::read_length_from(from, self.length_encoding_length)?;
+        let mut bytes = Vec::new();
+        self.encode_length_to(length, &mut bytes);
+        let offsets_length = (length * OFFSET_ENCODING_LENGTH) as u64;
+        (&mut *from).take(offsets_length).read_to_end(&mut bytes).map_err(Error::from_read_error)?;
+        if (bytes.len() as u64) < self.length_encoding_length as u64 + offsets_length {
+            return Err(Error::encoder__read_from__unexpected_eof);
+        }
+        let nests_length = if length == 0 {
+            0
+        } else {
+            self.offset_at(&bytes[self.length_encoding_length..], length - 1)
+        };
+        (&mut *from).take(nests_length as u64).read_to_end(&mut bytes).map_err(Error::from_read_error)?;
+        if bytes.len() < self.length_encoding_length + OFFSET_ENCODING_LENGTH * length + nests_length {
+            return Err(Error::encoder__read_from__unexpected_eof);
+        }
+        let (nest, _) = self.decode_with_remainder(&bytes)?;
+        Ok(nest.to_owned_nest())
+    }
+    fn decode_with_remainder<'a>(&self, bytes: &'a [u8]) -> Result<(Nest<'a>, &'a [u8]), Error> {
+        let (length, offsets, nests_bytes) = self.decode_header(bytes)?;
+        let mut nests = Vec::new();
+        for index in 0..length {
+            let (start, end) = self.range_at(offsets, index);
+            nests.push(self.decode_exactly(&nests_bytes[start..end])?);
+        }
+        let nests_length = if length == 0 { 0 } else { self.offset_at(offsets, length - 1) };
+        Ok((Nest::OwnedNests(nests), &nests_bytes[nests_length..]))
+    }
+    fn validate(&self, bytes: &[u8]) -> Result<usize, Error> {
+        let (length, offsets, nests_bytes) = self.decode_header(bytes)?;
+        for index in 0..length {
+            let (start, end) = self.range_at(offsets, index);
+            if self.template.encoder().validate(&nests_bytes[start..end])? != end - start {
+                return Err(Error::ilist__decode_with_remainder__nest_length_should_match_offsets);
+            }
+        }
+        let nests_length = if length == 0 { 0 } else { self.offset_at(offsets, length - 1) };
+        Ok(bytes.len() - nests_bytes.len() + nests_length)
+    }
+    fn skip(&self, bytes: &[u8]) -> Result<usize, Error> {
+        let length = self.decode_length(bytes)?;
+        let offsets_end = match length.checked_mul(OFFSET_ENCODING_LENGTH).and_then(|offsets_length| offsets_length.checked_add(self.length_encoding_length)) {
+            Some(offsets_end) if offsets_end <= bytes.len() => offsets_end,
+            _ => return Err(Error::ilist__decode_with_remainder__bytes_length_should_be_gte_offsets_length)
+        };
+        let nests_length = if length == 0 {
+            0
+        } else {
+            self.offset_at(&bytes[self.length_encoding_length..], length - 1)
+        };
+        if offsets_end + nests_length > bytes.len() {
+            Err(Error::ilist__decode_with_remainder__offsets_should_be_lte_bytes_length)
+        } else {
+            Ok(offsets_end + nests_length)
+        }
+    }
+    fn locate(&self, encoding: &[u8], start: usize, path: &[usize], length_fields: &mut Vec<LengthField>) -> Result<(&dyn Encoder, usize), Error> {
+        if start > encoding.len() {
+            return Err(Error::encoder__locate__start_should_be_lte_encoding_length);
+        }
+        if path.is_empty() {
+            return Ok((self, start));
+        }
+        let (length, offsets, _) = self.decode_header(&encoding[start..])?;
+        if path[0] >= length {
+            return Err(Error::encoder__locate__index_should_be_lt_length);
+        }
+        let offsets_start = start + self.length_encoding_length;
+        for index in path[0]..length {
+            length_fields.push(LengthField {
+                position: offsets_start + index * OFFSET_ENCODING_LENGTH,
+                length_encoding_length: OFFSET_ENCODING_LENGTH,
+                max_length: MAX_NESTS_LENGTH
+            });
+        }
+        let nest_start = offsets_start + offsets.len() + self.range_at(offsets, path[0]).0;
+        self.template.encoder().locate(encoding, nest_start, &path[1..], length_fields)
+    }
+    fn export_jinyang_to(&self, to: &mut Vec<u8>) {
+        self.template.export_jinyang_to(to);
+    }
+    fn export_canonical_jinyang_to(&self, to: &mut Vec<u8>) {
+        self.template.export_canonical_jinyang_to(to);
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template_ids::TemplateId;
+    use template::Template;
+    use encoders::ilist::Ilist;
+    use error::Error;
+    use nest::Nest;
+    use fixtures::{fixed, dynamic, ilist};
+
+    #[test]
+    fn should_throw_error_when_creating_ilist_with_dlist_template_id() {
+        assert_eq!(
+            Ilist::new(TemplateId::DlistAlpha, dynamic(TemplateId::DynamicAlpha)).err(),
+            Some(Error::ilist__new__invalid_template_id)
+        );
+    }
+
+    #[test]
+    fn should_encode_decode() {
+        let template = ilist(TemplateId::IlistAlpha, dynamic(TemplateId::DynamicAlpha));
+        let nest = Nest::Nests(&[
+            &Nest::Bytes(&[1, 2]),
+            &Nest::Bytes(&[]),
+            &Nest::Bytes(&[3])
+        ]);
+        let encoding = vec![3, 3, 0, 0, 0, 4, 0, 0, 0, 6, 0, 0, 0, 2, 1, 2, 0, 1, 3];
+        assert_eq!(template.encode(&nest), Ok(encoding.clone()));
+        assert_eq!(template.encoded_len(&nest), Ok(encoding.len()));
+        assert_eq!(template.decode(&encoding), Ok(nest));
+        assert_eq!(template.validate(&encoding), Ok(encoding.len()));
+        assert_eq!(template.skip(&encoding), Ok(encoding.len()));
+
+        let mut written = vec![];
+        assert_eq!(template.encode_to_writer(&template.decode(&encoding).unwrap(), &mut written), Ok(()));
+        assert_eq!(written, encoding);
+        assert_eq!(
+            template.decode_from_reader(&mut &encoding[..]),
+            Ok(Some(template.decode(&encoding).unwrap()))
+        );
+    }
+
+    #[test]
+    fn should_get_by_index() {
+        let template = ilist(TemplateId::IlistBeta, dynamic(TemplateId::DynamicAlpha));
+        let ilist : &Ilist = template.encoder().as_any().downcast_ref().unwrap();
+        let encoding = template.encode(&Nest::Nests(&[
+            &Nest::Bytes(&[1, 2]),
+            &Nest::Bytes(&[3, 4, 5]),
+            &Nest::Bytes(&[6])
+        ])).unwrap();
+        assert_eq!(ilist.get(&encoding, 1), Ok(Nest::Bytes(&[3, 4, 5])));
+        assert_eq!(ilist.get(&encoding, 2), Ok(Nest::Bytes(&[6])));
+        assert_eq!(ilist.get(&encoding, 3), Err(Error::ilist__get__index_should_be_lt_length));
+    }
+
+    #[test]
+    fn should_error_when_offsets_are_invalid() {
+        let template = ilist(TemplateId::IlistAlpha, dynamic(TemplateId::DynamicAlpha));
+        assert_eq!(
+            ilist(TemplateId::IlistBeta, dynamic(TemplateId::DynamicAlpha)).decode(&[2]),
+            Err(Error::ilist__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length)
+        );
+        assert_eq!(
+            template.skip(&[]),
+            Err(Error::ilist__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length)
+        );
+        assert_eq!(
+            template.decode(&[2, 2, 0, 0, 0, 1, 0, 0]),
+            Err(Error::ilist__decode_with_remainder__bytes_length_should_be_gte_offsets_length)
+        );
+        assert_eq!(
+            template.decode(&[2, 2, 0, 0, 0, 1, 0, 0, 0, 1, 9, 0]),
+            Err(Error::ilist__decode_with_remainder__offsets_should_be_ascending)
+        );
+        assert_eq!(
+            template.decode(&[1, 3, 0, 0, 0, 1, 9]),
+            Err(Error::ilist__decode_with_remainder__offsets_should_be_lte_bytes_length)
+        );
+        assert_eq!(
+            template.decode(&[1, 3, 0, 0, 0, 1, 9, 0]),
+            Err(Error::ilist__decode_with_remainder__nest_length_should_match_offsets)
+        );
+        assert_eq!(
+            template.validate(&[1, 3, 0, 0, 0, 1, 9, 0]),
+            Err(Error::ilist__decode_with_remainder__nest_length_should_match_offsets)
+        );
+    }
+
+    #[test]
+    fn should_not_limit_nests_encoding_to_max_length() {
+        let template = ilist(TemplateId::IlistAlpha, fixed(128));
+        let nest = Nest::Nests(&[&Nest::Bytes(&[0; 128]), &Nest::Bytes(&[0; 128])]);
+        let encoding = template.encode(&nest).unwrap();
+        assert_eq!(&encoding[..9], &[2, 128, 0, 0, 0, 0, 1, 0, 0]);
+        assert_eq!(template.decode(&encoding), Ok(nest));
+        assert_eq!(template.size_bounds().max, Some(1 + 255 * 4 + 255 * 128));
+    }
+
+    #[test]
+    fn should_jinyang() {
+        let template = Template::from_jinyang(&[11, 0, 31]).unwrap();
+        let ilist : &Ilist = template.encoder().as_any().downcast_ref().unwrap();
+        assert_eq!(template.id(), 11);
+        assert_eq!(ilist.length_encoding_length(), 2);
+        assert_eq!(ilist.template().id(), 0);
+        assert_eq!(template.export_jinyang(), vec![11, 0, 31]);
+    }
+
+}
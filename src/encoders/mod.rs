@@ -1,3 +1,9 @@
 pub mod fixed;
 pub mod dynamic;
 pub mod dlist;
+pub mod ilist;
+pub mod tuple;
+pub mod etuple;
+pub mod defaulted;
+pub mod choice;
+pub mod optional;
@@ -0,0 +1,210 @@
+use error::Error;
+use traits::encoder::Encoder;
+use template_ids::TemplateId;
+use template::Template;
+use std::io::{Read, Write};
+use std::any::Any;
+use nest::Nest;
+use size_bounds::SizeBounds;
+use patch::LengthField;
+use template_kind::TemplateKind;
+
+/// A value that may be absent, encoded as a presence byte, 0 or 1, and then
+/// the value if it is present. Values are like a list of at most one
+/// element: no children, or the value.
+///
+/// The jinyang is the template of the value. The default is absent.
+pub struct Optional {
+    template: Template
+}
+
+impl Optional {
+    pub fn new(template: Template) -> Optional {
+        Optional { template }
+    }
+    pub fn from_jinyang_with_remainder(_template_id: TemplateId, jinyang: &[u8], depth: usize) -> Result<(Optional, &[u8]), Error> {
+        let (template, remainder) = Template::from_jinyang_at_depth(jinyang, depth + 1)?;
+        Ok((Optional::new(template), remainder))
+    }
+    pub fn template(&self) -> &Template {
+        &self.template
+    }
+    /// The value of `nest`, if it has one.
+    fn value<'b, 'a>(&self, nest: &'b Nest<'a>) -> Result<Option<&'b Nest<'a>>, Error> {
+        let mut nests = nest.try_nests()?;
+        if nests.len() > 1 {
+            return Err(Error::optional__encode_to__nests_length_should_be_lte_1);
+        }
+        Ok(nests.next())
+    }
+    /// Whether the value at the start of `bytes` is present.
+    fn is_present(&self, bytes: &[u8]) -> Result<bool, Error> {
+        match bytes.first() {
+            None => Err(Error::optional__decode_with_remainder__bytes_length_should_be_gte_1),
+            Some(0) => Ok(false),
+            Some(1) => Ok(true),
+            Some(_) => Err(Error::optional__decode_with_remainder__invalid_presence_flag)
+        }
+    }
+}
+
+impl Encoder for Optional {
+    fn template_id(&self) -> u8 {
+        TemplateId::Optional as u8
+    }
+    fn kind(&self) -> TemplateKind<'_> {
+        TemplateKind::Optional {
+            template: &self.template
+        }
+    }
+    fn encode_to(&self, nest: &Nest, to: &mut Vec<u8>) -> Result<(), Error> {
+        match self.value(nest)? {
+            Some(value) => {
+                to.push(1);
+                self.template.encoder().encode_to(value, to)
+            },
+            None => {
+                to.push(0);
+                Ok(())
+            }
+        }
+    }
+    fn write_to(&self, nest: &Nest, to: &mut dyn Write) -> Result<(), Error> {
+        let value = self.value(nest)?;
+        to.write_all(&[value.is_some() as u8]).map_err(|error| Error::encoder__write_to__io_error(error.kind()))?;
+        match value {
+            Some(value) => self.template.encoder().write_to(value, to),
+            None => Ok(())
+        }
+    }
+    fn encoded_len(&self, nest: &Nest) -> Result<usize, Error> {
+        match self.value(nest)? {
+            Some(value) => Ok(1 + self.template.encoder().encoded_len(value)?),
+            None => Ok(1)
+        }
+    }
+    fn size_bounds(&self) -> SizeBounds {
+        SizeBounds {
+            min: 1,
+            max: self.template.size_bounds().max.and_then(|max| max.checked_add(1)),
+            payload_offset: Some(1)
+        }
+    }
+    fn default_nest(&self) -> Nest<'static> {
+        Nest::OwnedNests(vec![])
+    }
+    fn read_from(&self, from: &mut dyn Read) -> Result<Nest<'static>, Error> {
+        let mut presence = [0];
+        from.read_exact(&mut presence).map_err(Error::from_read_error)?;
+        if self.is_present(&presence)? {
+            Ok(Nest::OwnedNests(vec![self.template.encoder().read_from(from)?]))
+        } else {
+            Ok(Nest::OwnedNests(vec![]))
+        }
+    }
+    fn decode_with_remainder<'a>(&self, bytes: &'a [u8]) -> Result<(Nest<'a>, &'a [u8]), Error> {
+        if self.is_present(bytes)? {
+            let (value, remainder) = self.template.encoder().decode_with_remainder(&bytes[1..])?;
+            Ok((Nest::OwnedNests(vec![value]), remainder))
+        } else {
+            Ok((Nest::OwnedNests(vec![]), &bytes[1..]))
+        }
+    }
+    fn validate(&self, bytes: &[u8]) -> Result<usize, Error> {
+        if self.is_present(bytes)? {
+            Ok(1 + self.template.encoder().validate(&bytes[1..])?)
+        } else {
+            Ok(1)
+        }
+    }
+    fn skip(&self, bytes: &[u8]) -> Result<usize, Error> {
+        if self.is_present(bytes)? {
+            Ok(1 + self.template.encoder().skip(&bytes[1..])?)
+        } else {
+            Ok(1)
+        }
+    }
+    fn locate(&self, encoding: &[u8], start: usize, path: &[usize], length_fields: &mut Vec<LengthField>) -> Result<(&dyn Encoder, usize), Error> {
+        if start > encoding.len() {
+            return Err(Error::encoder__locate__start_should_be_lte_encoding_length);
+        }
+        if path.is_empty() {
+            return Ok((self, start));
+        }
+        if path[0] != 0 || !self.is_present(&encoding[start..])? {
+            return Err(Error::encoder__locate__index_should_be_lt_length);
+        }
+        self.template.encoder().locate(encoding, start + 1, &path[1..], length_fields)
+    }
+    fn export_jinyang_to(&self, to: &mut Vec<u8>) {
+        self.template.export_jinyang_to(to);
+    }
+    fn export_canonical_jinyang_to(&self, to: &mut Vec<u8>) {
+        self.template.export_canonical_jinyang_to(to);
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template::Template;
+    use error::Error;
+    use nest::Nest;
+
+    fn template() -> Template {
+        Template::from_schema("optional(dynamic<=255)").unwrap()
+    }
+
+    #[test]
+    fn should_encode_decode() {
+        let nest = Nest::Nests(&[&Nest::Bytes(&[2, 3])]);
+        assert_eq!(template().encode(&nest), Ok(vec![1, 2, 2, 3]));
+        assert_eq!(template().decode(&[1, 2, 2, 3]), Ok(nest));
+        assert_eq!(template().encode(&Nest::Nests(&[])), Ok(vec![0]));
+        assert_eq!(template().decode(&[0]), Ok(Nest::Nests(&[])));
+        assert_eq!(template().default_nest(), Nest::Nests(&[]));
+        assert_eq!(template().validate(&[1, 0, 9]), Ok(2));
+        assert_eq!(template().skip(&[0, 9]), Ok(1));
+        assert_eq!(template().size_bounds().min, 1);
+        assert_eq!(template().size_bounds().max, Some(257));
+        let mut reader = &[1u8, 1, 5][..];
+        assert_eq!(template().decode_from_reader(&mut reader), Ok(Some(Nest::Nests(&[&Nest::Bytes(&[5])]))));
+    }
+
+    #[test]
+    fn should_patch_present_value() {
+        let mut encoding = vec![1, 1, 5];
+        assert_eq!(template().patch(&mut encoding, &[0], &Nest::Bytes(&[6, 7])), Ok(()));
+        assert_eq!(encoding, vec![1, 2, 6, 7]);
+        let mut encoding = vec![0];
+        assert_eq!(
+            template().patch(&mut encoding, &[0], &Nest::Bytes(&[6])).err(),
+            Some(Error::encoder__locate__index_should_be_lt_length)
+        );
+        assert_eq!(encoding, vec![0]);
+    }
+
+    #[test]
+    fn should_jinyang() {
+        let template = Template::from_schema("optional(tuple(id:fixed(1)))").unwrap();
+        assert_eq!(template.export_jinyang(), vec![21, 14, 1, 0, 0, 1, 2, b'i', b'd', 0, 0]);
+        assert_eq!(template.export_canonical_jinyang(), vec![21, 14, 1, 0, 0, 0]);
+        assert_eq!(Template::from_jinyang(&[21, 2]).unwrap().to_schema(), "optional(dynamic<=255)");
+    }
+
+    #[test]
+    fn should_error_when_invalid() {
+        assert_eq!(
+            template().encode(&Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[2])])).err(),
+            Some(Error::optional__encode_to__nests_length_should_be_lte_1)
+        );
+        assert_eq!(template().encode(&Nest::Bytes(&[1])).err(), Some(Error::nest__try_nests__should_be_nests));
+        assert_eq!(template().decode(&[]).err(), Some(Error::optional__decode_with_remainder__bytes_length_should_be_gte_1));
+        assert_eq!(template().validate(&[2]).err(), Some(Error::optional__decode_with_remainder__invalid_presence_flag));
+        assert_eq!(Template::from_jinyang(&[21]).err(), Some(Error::template__from_jinyang__unexpected_end));
+    }
+
+}
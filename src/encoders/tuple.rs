@@ -0,0 +1,415 @@
+use error::Error;
+use traits::encoder::Encoder;
+use template_ids::TemplateId;
+use template::Template;
+use byteorder::{LittleEndian, WriteBytesExt, ReadBytesExt};
+use std::io::{Cursor, Read, Write};
+use std::any::Any;
+use nest::Nest;
+use size_bounds::SizeBounds;
+use patch::LengthField;
+use template_kind::TemplateKind;
+
+#[derive(Debug)]
+pub struct Field {
+    pub template: Template,
+    pub name: Option<String>,
+    pub doc: Option<String>,
+    /// Used when the field is absent. Only Etuple fields can have one.
+    pub default: Option<Nest<'static>>
+}
+
+impl Field {
+    pub fn new(template: Template) -> Field {
+        Field { template, name: None, doc: None, default: None }
+    }
+    pub fn named(name: &str, template: Template) -> Field {
+        Field { template, name: Some(name.to_string()), doc: None, default: None }
+    }
+    pub fn with_doc(mut self, doc: &str) -> Field {
+        self.doc = Some(doc.to_string());
+        self
+    }
+    pub fn with_default(mut self, default: Nest<'static>) -> Field {
+        self.default = Some(default);
+        self
+    }
+    /// The value to use when the field is left out: its own default, or the
+    /// one its Defaulted template gives. `None` if it cannot be left out.
+    pub fn default_nest(&self) -> Option<Nest<'static>> {
+        match self.default {
+            Some(ref default) => Some(default.clone()),
+            None if self.template.id() == TemplateId::Defaulted as u8 => Some(self.template.default_nest()),
+            None => None
+        }
+    }
+}
+
+/// Checks the field count and metadata shared by Tuple and Etuple.
+pub fn check_fields(fields: &[Field]) -> Result<(), Error> {
+    if fields.is_empty() {
+        return Err(Error::tuple__new__fields_length_should_be_gte_1);
+    }
+    if fields.len() > 255 {
+        return Err(Error::tuple__new__fields_length_should_be_lte_255);
+    }
+    for (index, field) in fields.iter().enumerate() {
+        if let Some(ref name) = field.name {
+            if name.is_empty() || name.len() > 255 {
+                return Err(Error::tuple__new__name_length_should_be_between_1_and_255);
+            }
+            if fields[..index].iter().any(|other| other.name.as_ref() == Some(name)) {
+                return Err(Error::tuple__new__names_should_be_unique);
+            }
+        }
+        if let Some(ref doc) = field.doc {
+            if doc.is_empty() || doc.len() > 65535 {
+                return Err(Error::tuple__new__doc_length_should_be_between_1_and_65535);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads the field count and field templates that start a Tuple or Etuple
+/// jinyang nested `depth` levels deep.
+pub fn fields_from_jinyang_with_remainder(jinyang: &[u8], depth: usize) -> Result<(Vec<Field>, &[u8]), Error> {
+    let (fields_length, mut remainder) = split_jinyang(jinyang, 1)?;
+    let mut fields = Vec::with_capacity(fields_length[0] as usize);
+    for _ in 0..fields_length[0] {
+        let (template, next_remainder) = Template::from_jinyang_at_depth(remainder, depth + 1)?;
+        fields.push(Field::new(template));
+        remainder = next_remainder;
+    }
+    Ok((fields, remainder))
+}
+
+/// Reads the metadata section that ends a Tuple or Etuple jinyang into
+/// `fields`.
+pub fn metadata_from_jinyang_with_remainder<'j>(fields: &mut [Field], jinyang: &'j [u8]) -> Result<&'j [u8], Error> {
+    let (has_metadata, mut remainder) = split_jinyang(jinyang, 1)?;
+    if has_metadata[0] == 1 {
+        for field in fields.iter_mut() {
+            let (name, next_remainder) = split_jinyang_string(remainder, 1)?;
+            let (doc, next_remainder) = split_jinyang_string(next_remainder, 2)?;
+            field.name = name;
+            field.doc = doc;
+            remainder = next_remainder;
+        }
+    } else if has_metadata[0] != 0 {
+        return Err(Error::tuple__from_jinyang__invalid_metadata_flag);
+    }
+    Ok(remainder)
+}
+
+pub fn export_metadata_to(fields: &[Field], to: &mut Vec<u8>) {
+    if fields.iter().any(|field| field.name.is_some() || field.doc.is_some()) {
+        to.push(1);
+        for field in fields {
+            export_jinyang_string_to(&field.name, 1, to);
+            export_jinyang_string_to(&field.doc, 2, to);
+        }
+    } else {
+        to.push(0);
+    }
+}
+
+/// A fixed sequence of fields, encoded back to back with no prefix.
+///
+/// The jinyang is the number of fields, each field's jinyang, then a
+/// metadata byte. When the metadata byte is 1 it is followed by every
+/// field's name (u8 length, then UTF-8) and doc (u16 length, then UTF-8),
+/// where a length of 0 means absent. Metadata never affects encodings.
+pub struct Tuple {
+    fields: Vec<Field>
+}
+
+impl Tuple {
+    pub fn new(fields: Vec<Field>) -> Result<Tuple, Error> {
+        check_fields(&fields)?;
+        if fields.iter().any(|field| field.default.is_some()) {
+            return Err(Error::tuple__new__fields_should_not_have_defaults);
+        }
+        Ok(Tuple { fields })
+    }
+    pub fn from_jinyang_with_remainder(_template_id: TemplateId, jinyang: &[u8], depth: usize) -> Result<(Tuple, &[u8]), Error> {
+        let (mut fields, remainder) = fields_from_jinyang_with_remainder(jinyang, depth)?;
+        let remainder = metadata_from_jinyang_with_remainder(&mut fields, remainder)?;
+        Ok((Tuple::new(fields)?, remainder))
+    }
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+    pub fn field_index(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|field| field.name.as_deref() == Some(name))
+    }
+    pub fn has_metadata(&self) -> bool {
+        self.fields.iter().any(|field| field.name.is_some() || field.doc.is_some())
+    }
+    fn nests<'b, 'a>(&self, nest: &'b Nest<'a>) -> Result<::nest::NestsIter<'b, 'a>, Error> {
+        let nests = nest.try_nests()?;
+        if nests.len() != self.fields.len() {
+            Err(Error::tuple__encode_to__nests_length_should_match_fields_length)
+        } else {
+            Ok(nests)
+        }
+    }
+}
+
+fn split_jinyang(jinyang: &[u8], length: usize) -> Result<(&[u8], &[u8]), Error> {
+    if jinyang.len() < length {
+        Err(Error::tuple__from_jinyang__unexpected_end)
+    } else {
+        Ok(jinyang.split_at(length))
+    }
+}
+
+fn split_jinyang_string(jinyang: &[u8], length_encoding_length: usize) -> Result<(Option<String>, &[u8]), Error> {
+    let (length_encoding, remainder) = split_jinyang(jinyang, length_encoding_length)?;
+    let mut length_bytes = [0; 2];
+    length_bytes[..length_encoding_length].copy_from_slice(length_encoding);
+    let length = Cursor::new(&length_bytes).read_u16::<LittleEndian>().unwrap() as usize;
+    if length == 0 {
+        return Ok((None, remainder));
+    }
+    let (string, remainder) = split_jinyang(remainder, length)?;
+    match String::from_utf8(string.to_vec()) {
+        Ok(string) => Ok((Some(string), remainder)),
+        Err(_) => Err(Error::tuple__from_jinyang__metadata_should_be_utf8)
+    }
+}
+
+fn export_jinyang_string_to(string: &Option<String>, length_encoding_length: usize, to: &mut Vec<u8>) {
+    let string = string.as_deref().unwrap_or("");
+    let mut length_encoding = Vec::with_capacity(2);
+    length_encoding.write_u16::<LittleEndian>(string.len() as u16).unwrap();
+    to.extend_from_slice(&length_encoding[..length_encoding_length]);
+    to.extend_from_slice(string.as_bytes());
+}
+
+impl Encoder for Tuple {
+    fn template_id(&self) -> u8 {
+        TemplateId::Tuple as u8
+    }
+    fn kind(&self) -> TemplateKind<'_> {
+        TemplateKind::Tuple {
+            fields: &self.fields
+        }
+    }
+    fn encode_to(&self, nest: &Nest, to: &mut Vec<u8>) -> Result<(), Error> {
+        for (field, nest) in self.fields.iter().zip(self.nests(nest)?) {
+            field.template.encoder().encode_to(nest, to)?;
+        }
+        Ok(())
+    }
+    fn write_to(&self, nest: &Nest, to: &mut dyn Write) -> Result<(), Error> {
+        for (field, nest) in self.fields.iter().zip(self.nests(nest)?) {
+            field.template.encoder().write_to(nest, to)?;
+        }
+        Ok(())
+    }
+    fn encoded_len(&self, nest: &Nest) -> Result<usize, Error> {
+        let mut length = 0;
+        for (field, nest) in self.fields.iter().zip(self.nests(nest)?) {
+            length += field.template.encoder().encoded_len(nest)?;
+        }
+        Ok(length)
+    }
+    fn size_bounds(&self) -> SizeBounds {
+        let mut min = 0;
+        let mut max = Some(0);
+        for field in &self.fields {
+            let bounds = field.template.size_bounds();
+            min += bounds.min;
+            max = max.and_then(|max: usize| bounds.max.and_then(|field_max| max.checked_add(field_max)));
+        }
+        SizeBounds {
+            min,
+            max,
+            payload_offset: Some(0)
+        }
+    }
+    fn default_nest(&self) -> Nest<'static> {
+        Nest::OwnedNests(self.fields.iter().map(|field| field.template.default_nest()).collect())
+    }
+    fn read_from(&self, from: &mut dyn Read) -> Result<Nest<'static>, Error> {
+        let mut nests = Vec::with_capacity(self.fields.len());
+        for field in &self.fields {
+            nests.push(field.template.encoder().read_from(from)?);
+        }
+        Ok(Nest::OwnedNests(nests))
+    }
+    fn decode_with_remainder<'a>(&self, bytes: &'a [u8]) -> Result<(Nest<'a>, &'a [u8]), Error> {
+        let mut nests = Vec::with_capacity(self.fields.len());
+        let mut remainder = bytes;
+        for field in &self.fields {
+            let (nest, next_remainder) = field.template.encoder().decode_with_remainder(remainder)?;
+            nests.push(nest);
+            remainder = next_remainder;
+        }
+        Ok((Nest::OwnedNests(nests), remainder))
+    }
+    fn validate(&self, bytes: &[u8]) -> Result<usize, Error> {
+        let mut consumed = 0;
+        for field in &self.fields {
+            consumed += field.template.encoder().validate(&bytes[consumed..])?;
+        }
+        Ok(consumed)
+    }
+    fn skip(&self, bytes: &[u8]) -> Result<usize, Error> {
+        let mut consumed = 0;
+        for field in &self.fields {
+            consumed += field.template.encoder().skip(&bytes[consumed..])?;
+        }
+        Ok(consumed)
+    }
+    fn locate(&self, encoding: &[u8], start: usize, path: &[usize], length_fields: &mut Vec<LengthField>) -> Result<(&dyn Encoder, usize), Error> {
+        if start > encoding.len() {
+            return Err(Error::encoder__locate__start_should_be_lte_encoding_length);
+        }
+        if path.is_empty() {
+            return Ok((self, start));
+        }
+        if path[0] >= self.fields.len() {
+            return Err(Error::encoder__locate__index_should_be_lt_length);
+        }
+        let mut field_start = start;
+        for field in &self.fields[..path[0]] {
+            field_start += field.template.encoder().skip(&encoding[field_start..])?;
+        }
+        self.fields[path[0]].template.encoder().locate(encoding, field_start, &path[1..], length_fields)
+    }
+    fn export_jinyang_to(&self, to: &mut Vec<u8>) {
+        to.push(self.fields.len() as u8);
+        for field in &self.fields {
+            field.template.export_jinyang_to(to);
+        }
+        export_metadata_to(&self.fields, to);
+    }
+    fn export_canonical_jinyang_to(&self, to: &mut Vec<u8>) {
+        to.push(self.fields.len() as u8);
+        for field in &self.fields {
+            field.template.export_canonical_jinyang_to(to);
+        }
+        to.push(0);
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template_ids::TemplateId;
+    use template::Template;
+    use encoders::tuple::{Tuple, Field};
+    use error::Error;
+    use nest::Nest;
+    use fixtures::{fixed, dynamic};
+
+    #[test]
+    fn should_encode_decode() {
+        let template = Template::new(Box::new(Tuple::new(vec![
+            Field::new(fixed(2)),
+            Field::new(dynamic(TemplateId::DynamicAlpha))
+        ]).unwrap()));
+        let nest = Nest::Nests(&[&Nest::Bytes(&[1, 2]), &Nest::Bytes(&[3])]);
+        assert_eq!(template.encode(&nest), Ok(vec![1, 2, 1, 3]));
+        assert_eq!(template.decode(&[1, 2, 1, 3]), Ok(nest));
+        assert_eq!(template.validate(&[1, 2, 1, 3]), Ok(4));
+        assert_eq!(template.skip(&[1, 2, 1, 3, 9]), Ok(4));
+        assert_eq!(template.size_bounds().min, 3);
+        assert_eq!(template.size_bounds().max, Some(258));
+        assert_eq!(
+            template.encode(&Nest::Nests(&[&Nest::Bytes(&[1, 2])])),
+            Err(Error::tuple__encode_to__nests_length_should_match_fields_length)
+        );
+    }
+
+    #[test]
+    fn should_patch_field() {
+        let template = Template::new(Box::new(Tuple::new(vec![
+            Field::new(dynamic(TemplateId::DynamicAlpha)),
+            Field::new(fixed(1))
+        ]).unwrap()));
+        let mut encoding = template.encode(&Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[2])])).unwrap();
+        assert_eq!(template.patch(&mut encoding, &[1], &Nest::Bytes(&[7])), Ok(()));
+        assert_eq!(encoding, vec![1, 1, 7]);
+    }
+
+    #[test]
+    fn should_jinyang() {
+        let jinyang = vec![14, 2, 0, 31, 2, 0];
+        let template = Template::from_jinyang(&jinyang).unwrap();
+        assert_eq!(template.id(), 14);
+        assert_eq!(template.export_jinyang(), jinyang);
+    }
+
+    #[test]
+    fn should_jinyang_metadata() {
+        let template = Template::new(Box::new(Tuple::new(vec![
+            Field::named("id", fixed(1)).with_doc("Record id"),
+            Field::new(dynamic(TemplateId::DynamicAlpha))
+        ]).unwrap()));
+        let jinyang = template.export_jinyang();
+        assert_eq!(jinyang, vec![
+            14, 2, 0, 0, 2, 1,
+            2, b'i', b'd', 9, 0, b'R', b'e', b'c', b'o', b'r', b'd', b' ', b'i', b'd',
+            0, 0, 0
+        ]);
+        let parsed = Template::from_jinyang(&jinyang).unwrap();
+        let tuple : &Tuple = parsed.encoder().as_any().downcast_ref().unwrap();
+        assert_eq!(tuple.fields()[0].name, Some("id".to_string()));
+        assert_eq!(tuple.fields()[0].doc, Some("Record id".to_string()));
+        assert_eq!(tuple.fields()[1].name, None);
+        assert_eq!(tuple.field_index("id"), Some(0));
+        assert_eq!(parsed.export_jinyang(), jinyang);
+    }
+
+    #[test]
+    fn should_name_paths() {
+        let template = Template::from_schema("tuple(items:dlist<=255(tuple(fixed(1),id:fixed(2))),dynamic<=255)").unwrap();
+        assert_eq!(template.path_to_string(&[0, 2, 1]), ".items[2].id");
+        assert_eq!(template.path_to_string(&[1]), "[1]");
+        assert_eq!(template.path_to_string(&[0, 2, 0]), ".items[2][0]");
+        assert_eq!(template.path_to_string(&[]), "[]");
+        assert_eq!(template.kind().child_name(0), Some("items"));
+        assert_eq!(template.kind().child_template(1).unwrap().id(), 2);
+    }
+
+    #[test]
+    fn should_fingerprint_without_metadata() {
+        let named = Template::from_schema("dlist<=255(tuple(id\"Record id\":fixed(1),dynamic<=255))").unwrap();
+        let unnamed = Template::from_schema("dlist<=255(tuple(fixed(1),dynamic<=255))").unwrap();
+        assert_ne!(named.export_jinyang(), unnamed.export_jinyang());
+        assert_eq!(named.export_canonical_jinyang(), unnamed.export_jinyang());
+        assert_eq!(named.fingerprint(), unnamed.fingerprint());
+        assert_eq!(Template::from_schema("tuple(a:fixed(1),b:dynamic<=255)").unwrap().fingerprint(), [
+            68, 8, 249, 106, 92, 56, 95, 234, 123, 153, 172, 232, 187, 103, 222, 128,
+            0, 44, 67, 130, 51, 194, 21, 171, 182, 215, 51, 16, 14, 80, 232, 252
+        ]);
+        assert_ne!(
+            Template::from_schema("tuple(fixed(1),dynamic<=65535)").unwrap().fingerprint(),
+            Template::from_schema("tuple(fixed(1),dynamic<=255)").unwrap().fingerprint()
+        );
+    }
+
+    #[test]
+    fn should_error_when_invalid() {
+        assert_eq!(Tuple::new(vec![]).err(), Some(Error::tuple__new__fields_length_should_be_gte_1));
+        assert_eq!(
+            Tuple::new(vec![Field::named("a", fixed(1)), Field::named("a", fixed(1))]).err(),
+            Some(Error::tuple__new__names_should_be_unique)
+        );
+        assert_eq!(
+            Template::from_jinyang_with_remainder(&[14, 1, 2]).err(),
+            Some(Error::tuple__from_jinyang__unexpected_end)
+        );
+        assert_eq!(
+            Template::from_jinyang_with_remainder(&[14, 1, 2, 1, 1, 0xff, 0, 0]).err(),
+            Some(Error::tuple__from_jinyang__metadata_should_be_utf8)
+        );
+    }
+
+}
@@ -0,0 +1,203 @@
+//! Self-describing messages: a header naming the template, then the value
+//! encoded with it.
+//!
+//! ```text
+//! [version = 1] [kind = 0] [jinyang]          [encoding]
+//! [version = 1] [kind = 1] [fingerprint x 32] [encoding]
+//! ```
+//!
+//! The jinyang is the one `Template::export_jinyang` returns, so a reader
+//! needs nothing but the bytes. A fingerprint header is 32 bytes however big
+//! the template is, but the reader has to know the template already, see
+//! `Registry`.
+
+use error::Error;
+use template::Template;
+use nest::Nest;
+use std::collections::HashMap;
+
+pub const VERSION: u8 = 1;
+
+const KIND_JINYANG: u8 = 0;
+const KIND_FINGERPRINT: u8 = 1;
+
+pub enum Header {
+    Jinyang(Template),
+    Fingerprint([u8; 32])
+}
+
+pub fn encode_with_jinyang(template: &Template, nest: &Nest) -> Result<Vec<u8>, Error> {
+    let mut envelope = vec![VERSION, KIND_JINYANG];
+    template.export_jinyang_to(&mut envelope);
+    envelope.extend_from_slice(&template.encode(nest)?);
+    Ok(envelope)
+}
+
+pub fn encode_with_fingerprint(template: &Template, nest: &Nest) -> Result<Vec<u8>, Error> {
+    let mut envelope = vec![VERSION, KIND_FINGERPRINT];
+    envelope.extend_from_slice(&template.fingerprint());
+    envelope.extend_from_slice(&template.encode(nest)?);
+    Ok(envelope)
+}
+
+/// Splits an envelope into its header and the encoding that follows it.
+pub fn read_header(envelope: &[u8]) -> Result<(Header, &[u8]), Error> {
+    if envelope.len() < 2 {
+        return Err(Error::envelope__read_header__unexpected_end);
+    }
+    if envelope[0] != VERSION {
+        return Err(Error::envelope__read_header__unsupported_version);
+    }
+    match envelope[1] {
+        KIND_JINYANG => {
+            let (template, encoding) = Template::from_jinyang_with_remainder(&envelope[2..])?;
+            Ok((Header::Jinyang(template), encoding))
+        },
+        KIND_FINGERPRINT => {
+            if envelope.len() < 34 {
+                return Err(Error::envelope__read_header__unexpected_end);
+            }
+            let mut fingerprint = [0; 32];
+            fingerprint.copy_from_slice(&envelope[2..34]);
+            Ok((Header::Fingerprint(fingerprint), &envelope[34..]))
+        },
+        _ => Err(Error::envelope__read_header__invalid_kind)
+    }
+}
+
+/// Decodes an envelope that carries its jinyang. Fingerprint envelopes need
+/// a `Registry`.
+pub fn decode(envelope: &[u8]) -> Result<(Template, Nest<'_>), Error> {
+    match read_header(envelope)? {
+        (Header::Jinyang(template), encoding) => {
+            let nest = template.decode(encoding)?;
+            Ok((template, nest))
+        },
+        (Header::Fingerprint(_), _) => Err(Error::envelope__decode__unknown_fingerprint)
+    }
+}
+
+/// Templates keyed by fingerprint, for decoding envelopes of either kind.
+#[derive(Default)]
+pub struct Registry {
+    templates: HashMap<[u8; 32], Template>
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+    /// Adds `template` unless one with the same fingerprint is already
+    /// present, and returns the fingerprint.
+    pub fn insert(&mut self, template: Template) -> [u8; 32] {
+        let fingerprint = template.fingerprint();
+        self.templates.entry(fingerprint).or_insert(template);
+        fingerprint
+    }
+    pub fn get(&self, fingerprint: &[u8; 32]) -> Option<&Template> {
+        self.templates.get(fingerprint)
+    }
+    /// Decodes an envelope of either kind. Templates read from jinyang
+    /// headers are used once and dropped, so untrusted envelopes cannot grow
+    /// the registry; see `decode_and_insert`.
+    pub fn decode<'a>(&self, envelope: &'a [u8]) -> Result<Nest<'a>, Error> {
+        match read_header(envelope)? {
+            (Header::Jinyang(template), encoding) => template.decode(encoding),
+            (Header::Fingerprint(fingerprint), encoding) => self.templates.get(&fingerprint)
+                .ok_or(Error::envelope__decode__unknown_fingerprint)?
+                .decode(encoding)
+        }
+    }
+    /// Like `decode`, but templates read from jinyang headers are added, so
+    /// later fingerprint envelopes can refer to them. Only use it on
+    /// envelopes from trusted senders.
+    pub fn decode_and_insert<'a>(&mut self, envelope: &'a [u8]) -> Result<(&Template, Nest<'a>), Error> {
+        let (header, encoding) = read_header(envelope)?;
+        let fingerprint = match header {
+            Header::Jinyang(template) => self.insert(template),
+            Header::Fingerprint(fingerprint) => fingerprint
+        };
+        let template = self.templates.get(&fingerprint)
+            .ok_or(Error::envelope__decode__unknown_fingerprint)?;
+        Ok((template, template.decode(encoding)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template::Template;
+    use envelope::{encode_with_jinyang, encode_with_fingerprint, decode, read_header, Header, Registry};
+    use error::Error;
+    use nest::Nest;
+
+    fn template() -> Template {
+        Template::from_schema("tuple(id:fixed(1),dlist<=255(dynamic<=255))").unwrap()
+    }
+
+    fn nest() -> Nest<'static> {
+        Nest::Nests(&[&Nest::Bytes(&[7]), &Nest::Nests(&[&Nest::Bytes(&[1, 2])])])
+    }
+
+    #[test]
+    fn should_round_trip_jinyang() {
+        let envelope = encode_with_jinyang(&template(), &nest()).unwrap();
+        assert_eq!(&envelope[..2], &[1, 0]);
+        let (template, decoded) = decode(&envelope).unwrap();
+        assert_eq!(template.export_jinyang(), self::template().export_jinyang());
+        assert_eq!(decoded, nest());
+    }
+
+    #[test]
+    fn should_round_trip_fingerprint() {
+        let envelope = encode_with_fingerprint(&template(), &nest()).unwrap();
+        assert_eq!(envelope.len(), 34 + 5);
+        match read_header(&envelope).unwrap() {
+            (Header::Fingerprint(fingerprint), encoding) => {
+                assert_eq!(fingerprint, template().fingerprint());
+                assert_eq!(encoding, &[7, 1, 2, 1, 2]);
+            },
+            _ => panic!()
+        }
+        assert_eq!(decode(&envelope).err(), Some(Error::envelope__decode__unknown_fingerprint));
+        assert_eq!(Registry::new().decode(&envelope).err(), Some(Error::envelope__decode__unknown_fingerprint));
+        let mut registry = Registry::new();
+        registry.insert(template());
+        assert_eq!(registry.decode(&envelope), Ok(nest()));
+    }
+
+    #[test]
+    fn should_register_templates_from_jinyang_only_when_asked() {
+        let mut registry = Registry::new();
+        let jinyang_envelope = encode_with_jinyang(&template(), &nest()).unwrap();
+        assert_eq!(registry.decode(&jinyang_envelope), Ok(nest()));
+        assert!(registry.get(&template().fingerprint()).is_none());
+        registry.decode_and_insert(&jinyang_envelope).unwrap();
+        assert!(registry.get(&template().fingerprint()).is_some());
+        let envelope = encode_with_fingerprint(&template(), &nest()).unwrap();
+        assert_eq!(registry.decode(&envelope), Ok(nest()));
+    }
+
+    #[test]
+    fn should_error_when_invalid() {
+        assert_eq!(decode(&[1]).err(), Some(Error::envelope__read_header__unexpected_end));
+        assert_eq!(decode(&[2, 0, 2]).err(), Some(Error::envelope__read_header__unsupported_version));
+        assert_eq!(decode(&[1, 2, 2]).err(), Some(Error::envelope__read_header__invalid_kind));
+        assert_eq!(decode(&[1, 1, 0]).err(), Some(Error::envelope__read_header__unexpected_end));
+        assert_eq!(decode(&[1, 0]).err(), Some(Error::template__from_jinyang__unexpected_end));
+        assert_eq!(decode(&[1, 0, 200]).err(), Some(Error::template__from_jinyang__invalid_template_id));
+        assert_eq!(decode(&[1, 0, 1, 0]).err(), Some(Error::fixed__from_jinyang__unexpected_end));
+        assert_eq!(
+            decode(&[1, 0, 2, 1, 5, 6]).err(),
+            Some(Error::template__decode__should_not_have_any_remainder)
+        );
+        let mut envelope = vec![1, 0];
+        envelope.extend_from_slice(&vec![6; 200000]);
+        envelope.push(2);
+        assert_eq!(
+            decode(&envelope).err(),
+            Some(Error::template__from_jinyang__depth_should_be_lte_max_depth)
+        );
+    }
+
+}
@@ -1,3 +1,6 @@
+use std::io::{self, ErrorKind};
+use std::{error, fmt};
+
 #[derive(Debug, PartialEq)]
 pub enum Error {
     template__decode__should_not_have_any_remainder,
@@ -11,5 +14,114 @@ pub enum Error {
     dynamic__new__invalid_template_id,
     dynamic__encode_to__bytes_length_should_be_lte_max_length,
     dynamic__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length,
-    dynamic__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length_plus_length
+    dynamic__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length_plus_length,
+    dlist__encode_to__nests_length_should_be_lte_max_length,
+    encoder__write_to__io_error(ErrorKind),
+    encoder__read_from__unexpected_eof,
+    encoder__read_from__io_error(ErrorKind),
+    lazy_list__get__index_should_be_lt_length,
+    ilist__new__invalid_template_id,
+    ilist__encode_to__nests_length_should_be_lte_max_length,
+    ilist__encode_to__nests_encoding_length_should_be_lte_max_length,
+    ilist__decode_with_remainder__bytes_length_should_be_gte_offsets_length,
+    ilist__decode_with_remainder__offsets_should_be_ascending,
+    ilist__decode_with_remainder__offsets_should_be_lte_bytes_length,
+    ilist__decode_with_remainder__nest_length_should_match_offsets,
+    ilist__get__index_should_be_lt_length,
+    encoder__locate__path_should_end_at_bytes,
+    encoder__locate__index_should_be_lt_length,
+    template__patch__length_should_be_lte_max_length,
+    template__patch__length_field_should_cover_value,
+    nest__try_bytes__should_be_bytes,
+    nest__try_nests__should_be_nests,
+    nest__child__index_should_be_lt_length,
+    visit__walk__nest_should_match_template(String),
+    schema__parse__unexpected_end,
+    schema__parse__unexpected_character(usize),
+    schema__parse__unknown_kind(usize),
+    schema__parse__invalid_length(usize),
+    schema__parse__should_not_have_any_remainder(usize),
+    tuple__new__fields_length_should_be_gte_1,
+    tuple__new__fields_length_should_be_lte_255,
+    tuple__new__name_length_should_be_between_1_and_255,
+    tuple__new__names_should_be_unique,
+    tuple__new__doc_length_should_be_between_1_and_65535,
+    tuple__from_jinyang__unexpected_end,
+    tuple__from_jinyang__invalid_metadata_flag,
+    tuple__from_jinyang__metadata_should_be_utf8,
+    tuple__encode_to__nests_length_should_match_fields_length,
+    fixed__from_jinyang__unexpected_end,
+    template__from_jinyang__unexpected_end,
+    template__from_jinyang__invalid_template_id,
+    envelope__read_header__unexpected_end,
+    envelope__read_header__unsupported_version,
+    envelope__read_header__invalid_kind,
+    envelope__decode__unknown_fingerprint,
+    migrate__apply__nest_should_match_template,
+    migrate__apply__mapping_should_match_template,
+    migrate__apply__index_should_be_lt_length,
+    migrate__apply__unknown_field_name,
+    tuple__new__fields_should_not_have_defaults,
+    etuple__new__invalid_template_id,
+    etuple__new__defaults_should_be_trailing,
+    etuple__from_jinyang__invalid_default_flag,
+    etuple__encode_to__nests_length_should_match_fields_length,
+    etuple__encode_to__body_length_should_be_lte_max_length,
+    etuple__decode_with_remainder__bytes_length_should_be_gte_length,
+    etuple__decode_with_remainder__missing_field_should_have_default,
+    etuple__locate__field_should_be_present,
+    schema__parse__invalid_default(usize),
+    vectors__parse__invalid_json,
+    vectors__parse__unsupported_version,
+    vectors__parse__invalid_vector(usize),
+    json__to_json__nest_should_match_template,
+    json__from_json__should_be_hex,
+    json__from_json__should_be_array,
+    json__from_json__should_be_object,
+    json__from_json__array_length_should_match_fields_length,
+    json__from_json__unknown_field_name,
+    json__from_json__missing_field,
+    json__from_str__invalid_json,
+    serialize__unsupported_type,
+    serialize__type_should_match_template,
+    serialize__length_should_match_template,
+    serialize__field_name_should_match_template,
+    serialize__custom(String),
+    dlist__locate__bytes_length_should_be_gte_nests_length,
+    template__from_jinyang__depth_should_be_lte_max_depth,
+    schema__parse__depth_should_be_lte_max_depth(usize),
+    ilist__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length,
+    etuple__split_body__bytes_length_should_be_gte_length_encoding_length,
+    encoder__locate__start_should_be_lte_encoding_length,
+    choice__new__variants_should_not_have_defaults,
+    choice__encode_to__nest_should_be_variant_and_value,
+    choice__encode_to__variant_should_be_lt_variants_length,
+    choice__decode_with_remainder__bytes_length_should_be_gte_1,
+    choice__decode_with_remainder__variant_should_be_lt_variants_length,
+    choice__locate__index_should_be_1,
+    json__from_json__should_be_variant_and_value,
+    json__from_json__unknown_variant,
+    optional__encode_to__nests_length_should_be_lte_1,
+    optional__decode_with_remainder__bytes_length_should_be_gte_1,
+    optional__decode_with_remainder__invalid_presence_flag,
+    serialize__variant_index_should_be_lt_variants_length,
+    serialize__variant_name_should_match_template,
 }
+
+impl Error {
+    pub fn from_read_error(error: io::Error) -> Error {
+        match error.kind() {
+            ErrorKind::UnexpectedEof => Error::encoder__read_from__unexpected_eof,
+            kind => Error::encoder__read_from__io_error(kind)
+        }
+    }
+}
+
+/// Writes the variant, whose name describes the error.
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl error::Error for Error {}
@@ -0,0 +1,25 @@
+//! Template constructors shared by the unit tests.
+
+use template_ids::TemplateId;
+use template::Template;
+use encoders::fixed::Fixed;
+use encoders::dynamic::Dynamic;
+use encoders::dlist::Dlist;
+use encoders::ilist::Ilist;
+
+pub fn fixed(length: usize) -> Template {
+    let template_id = if length > 256 { TemplateId::FixedBeta } else { TemplateId::FixedAlpha };
+    Template::new(Box::new(Fixed::new(template_id, length).unwrap()))
+}
+
+pub fn dynamic(template_id: TemplateId) -> Template {
+    Template::new(Box::new(Dynamic::new(template_id).unwrap()))
+}
+
+pub fn dlist(template_id: TemplateId, template: Template) -> Template {
+    Template::new(Box::new(Dlist::new(template_id, template).unwrap()))
+}
+
+pub fn ilist(template_id: TemplateId, template: Template) -> Template {
+    Template::new(Box::new(Ilist::new(template_id, template).unwrap()))
+}
@@ -0,0 +1,353 @@
+//! Converts nests to and from JSON with the help of their template, behind
+//! the `json` feature.
+//!
+//! Bytes are written as lowercase hex. Lists are arrays, and so are
+//! optionals, with no element or one. Tuples and etuples whose fields are
+//! all named are objects keyed by name, and arrays otherwise. Unknown data
+//! an etuple kept is its last array element, or the `""` key of its object,
+//! which no field can be named. Objects may leave out fields that have a
+//! default. Choices whose variants are all named are an object with the
+//! variant's name as its only key, and `[variant, value]` otherwise.
+//!
+//! There are no typed templates in this tree, such as strings or integers,
+//! so every leaf is hex.
+
+use error::Error;
+use template::Template;
+use template_kind::TemplateKind;
+use encoders::tuple::Field;
+use encoders::choice::nest_variant;
+use nest::Nest;
+use serde_json::{self, Value, Map};
+
+pub fn to_json(template: &Template, nest: &Nest) -> Result<Value, Error> {
+    let kind = template.kind();
+    match kind {
+        TemplateKind::Fixed { .. } | TemplateKind::Dynamic { .. } => Ok(bytes_to_json(nest.try_bytes()?)),
+        TemplateKind::Dlist { template, .. } | TemplateKind::Ilist { template, .. } | TemplateKind::Optional { template } => {
+            let mut values = Vec::with_capacity(nest.try_nests()?.len());
+            for child in nest.iter_nests() {
+                values.push(to_json(template, child)?);
+            }
+            Ok(Value::Array(values))
+        },
+        TemplateKind::Tuple { fields } | TemplateKind::Etuple { fields, .. } => {
+            let children = nest.try_nests()?.collect::<Vec<_>>();
+            if children.len() < fields.len() || children.len() > max_children(kind, fields) {
+                return Err(Error::json__to_json__nest_should_match_template);
+            }
+            let unknown = match children.get(fields.len()) {
+                Some(unknown) => Some(bytes_to_json(unknown.try_bytes()?)),
+                None => None
+            };
+            if is_named(fields) {
+                let mut object = Map::new();
+                for (field, child) in fields.iter().zip(children) {
+                    object.insert(field.name.clone().unwrap(), to_json(&field.template, child)?);
+                }
+                if let Some(unknown) = unknown {
+                    object.insert(String::new(), unknown);
+                }
+                Ok(Value::Object(object))
+            } else {
+                let mut values = Vec::with_capacity(children.len());
+                for (field, child) in fields.iter().zip(children) {
+                    values.push(to_json(&field.template, child)?);
+                }
+                values.extend(unknown);
+                Ok(Value::Array(values))
+            }
+        },
+        TemplateKind::Choice { variants } => {
+            let variant = nest_variant(nest)
+                .and_then(|variant| variants.get(variant).map(|field| (variant, field)));
+            let (variant, field) = variant.ok_or(Error::json__to_json__nest_should_match_template)?;
+            let value = to_json(&field.template, nest.child(1)?)?;
+            if is_named(variants) {
+                let mut object = Map::new();
+                object.insert(field.name.clone().unwrap(), value);
+                Ok(Value::Object(object))
+            } else {
+                Ok(Value::Array(vec![Value::from(variant), value]))
+            }
+        }
+    }
+}
+
+pub fn to_string(template: &Template, nest: &Nest) -> Result<String, Error> {
+    Ok(to_json(template, nest)?.to_string())
+}
+
+/// Builds an owned nest of `template` from `value`. The nest is not checked
+/// against the template's lengths; encoding it does that.
+pub fn from_json(template: &Template, value: &Value) -> Result<Nest<'static>, Error> {
+    let kind = template.kind();
+    match kind {
+        TemplateKind::Fixed { .. } | TemplateKind::Dynamic { .. } => Ok(Nest::OwnedBytes(bytes_from_json(value)?)),
+        TemplateKind::Dlist { template, .. } | TemplateKind::Ilist { template, .. } | TemplateKind::Optional { template } => {
+            let values = value.as_array().ok_or(Error::json__from_json__should_be_array)?;
+            let mut nests = Vec::with_capacity(values.len());
+            for value in values {
+                nests.push(from_json(template, value)?);
+            }
+            Ok(Nest::OwnedNests(nests))
+        },
+        TemplateKind::Tuple { fields } | TemplateKind::Etuple { fields, .. } => {
+            let can_be_unknown = max_children(kind, fields) > fields.len();
+            if is_named(fields) {
+                let object = value.as_object().ok_or(Error::json__from_json__should_be_object)?;
+                let mut nests = Vec::with_capacity(fields.len() + 1);
+                for field in fields {
+                    nests.push(match object.get(field.name.as_ref().unwrap()) {
+                        Some(value) => from_json(&field.template, value)?,
+                        None => field.default_nest().ok_or(Error::json__from_json__missing_field)?
+                    });
+                }
+                for key in object.keys() {
+                    if key.is_empty() && can_be_unknown {
+                        nests.push(Nest::OwnedBytes(bytes_from_json(&object[key])?));
+                    } else if !fields.iter().any(|field| field.name.as_ref() == Some(key)) {
+                        return Err(Error::json__from_json__unknown_field_name);
+                    }
+                }
+                Ok(Nest::OwnedNests(nests))
+            } else {
+                let values = value.as_array().ok_or(Error::json__from_json__should_be_array)?;
+                if values.len() < fields.len() || values.len() > max_children(kind, fields) {
+                    return Err(Error::json__from_json__array_length_should_match_fields_length);
+                }
+                let mut nests = Vec::with_capacity(values.len());
+                for (field, value) in fields.iter().zip(values) {
+                    nests.push(from_json(&field.template, value)?);
+                }
+                if values.len() > fields.len() {
+                    nests.push(Nest::OwnedBytes(bytes_from_json(&values[fields.len()])?));
+                }
+                Ok(Nest::OwnedNests(nests))
+            }
+        },
+        TemplateKind::Choice { variants } => {
+            let (variant, value) = if is_named(variants) {
+                let object = value.as_object().ok_or(Error::json__from_json__should_be_object)?;
+                if object.len() != 1 {
+                    return Err(Error::json__from_json__should_be_variant_and_value);
+                }
+                let (name, value) = object.iter().next().unwrap();
+                let variant = variants.iter().position(|variant| variant.name.as_ref() == Some(name))
+                    .ok_or(Error::json__from_json__unknown_variant)?;
+                (variant, value)
+            } else {
+                let values = value.as_array().ok_or(Error::json__from_json__should_be_array)?;
+                if values.len() != 2 {
+                    return Err(Error::json__from_json__should_be_variant_and_value);
+                }
+                let variant = match values[0].as_u64() {
+                    Some(variant) if (variant as usize) < variants.len() => variant as usize,
+                    _ => return Err(Error::json__from_json__unknown_variant)
+                };
+                (variant, &values[1])
+            };
+            Ok(Nest::OwnedNests(vec![
+                Nest::OwnedBytes(vec![variant as u8]),
+                from_json(&variants[variant].template, value)?
+            ]))
+        }
+    }
+}
+
+pub fn from_str(template: &Template, text: &str) -> Result<Nest<'static>, Error> {
+    let value: Value = serde_json::from_str(text).map_err(|_| Error::json__from_str__invalid_json)?;
+    from_json(template, &value)
+}
+
+/// How many children a nest of a Tuple or Etuple can have: one per field,
+/// and for an Etuple one more for unknown data.
+fn max_children(kind: TemplateKind, fields: &[Field]) -> usize {
+    match kind {
+        TemplateKind::Etuple { .. } => fields.len() + 1,
+        _ => fields.len()
+    }
+}
+
+fn is_named(fields: &[Field]) -> bool {
+    fields.iter().all(|field| field.name.is_some())
+}
+
+pub fn bytes_to_json(bytes: &[u8]) -> Value {
+    Value::String(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+pub fn bytes_from_json(value: &Value) -> Result<Vec<u8>, Error> {
+    let text = value.as_str().ok_or(Error::json__from_json__should_be_hex)?;
+    if text.len() % 2 == 1 || !text.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(Error::json__from_json__should_be_hex);
+    }
+    Ok(text.as_bytes().chunks(2).map(|pair| {
+        u8::from_str_radix(::std::str::from_utf8(pair).unwrap(), 16).unwrap()
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template::Template;
+    use json::{to_json, to_string, from_json, from_str};
+    use error::Error;
+    use nest::Nest;
+
+    fn assert_round_trip(schema: &str, nest: Nest, text: &str) {
+        let template = Template::from_schema(schema).unwrap();
+        assert_eq!(to_string(&template, &nest), Ok(text.to_string()));
+        assert_eq!(from_str(&template, text), Ok(nest));
+    }
+
+    #[test]
+    fn should_round_trip_bytes_and_lists() {
+        assert_round_trip("fixed(2)", Nest::Bytes(&[0, 255]), "\"00ff\"");
+        assert_round_trip("dynamic<=255", Nest::Bytes(&[]), "\"\"");
+        assert_round_trip(
+            "dlist<=255(ilist<=255(fixed(1)))",
+            Nest::Nests(&[&Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[2])]), &Nest::Nests(&[])]),
+            "[[\"01\",\"02\"],[]]"
+        );
+    }
+
+    #[test]
+    fn should_round_trip_tuples() {
+        assert_round_trip(
+            "tuple(id:fixed(1),tags:dlist<=255(dynamic<=255))",
+            Nest::Nests(&[&Nest::Bytes(&[7]), &Nest::Nests(&[&Nest::Bytes(&[1, 2])])]),
+            "{\"id\":\"07\",\"tags\":[\"0102\"]}"
+        );
+        assert_round_trip(
+            "tuple(id:fixed(1),dynamic<=255)",
+            Nest::Nests(&[&Nest::Bytes(&[7]), &Nest::Bytes(&[])]),
+            "[\"07\",\"\"]"
+        );
+    }
+
+    #[test]
+    fn should_round_trip_choices() {
+        assert_round_trip(
+            "choice(circle:fixed(1),square:dlist<=255(fixed(1)))",
+            Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Nests(&[&Nest::Bytes(&[2])])]),
+            "{\"square\":[\"02\"]}"
+        );
+        assert_round_trip(
+            "choice(circle:fixed(1),dynamic<=255)",
+            Nest::Nests(&[&Nest::Bytes(&[0]), &Nest::Bytes(&[7])]),
+            "[0,\"07\"]"
+        );
+        let template = Template::from_schema("choice(a:fixed(1),b:fixed(1))").unwrap();
+        assert_eq!(from_str(&template, "{\"c\":\"01\"}").err(), Some(Error::json__from_json__unknown_variant));
+        assert_eq!(
+            from_str(&template, "{\"a\":\"01\",\"b\":\"01\"}").err(),
+            Some(Error::json__from_json__should_be_variant_and_value)
+        );
+        assert_eq!(
+            to_json(&template, &Nest::Nests(&[&Nest::Bytes(&[2]), &Nest::Bytes(&[1])])).err(),
+            Some(Error::json__to_json__nest_should_match_template)
+        );
+        let template = Template::from_schema("choice(fixed(1),fixed(1))").unwrap();
+        assert_eq!(from_str(&template, "[2,\"01\"]").err(), Some(Error::json__from_json__unknown_variant));
+        assert_eq!(from_str(&template, "[0]").err(), Some(Error::json__from_json__should_be_variant_and_value));
+    }
+
+    #[test]
+    fn should_round_trip_optionals() {
+        assert_round_trip("optional(fixed(1))", Nest::Nests(&[&Nest::Bytes(&[7])]), "[\"07\"]");
+        assert_round_trip(
+            "optional(optional(fixed(1)))",
+            Nest::Nests(&[&Nest::Nests(&[])]),
+            "[[]]"
+        );
+        assert_round_trip("optional(optional(fixed(1)))", Nest::Nests(&[]), "[]");
+    }
+
+    #[test]
+    fn should_round_trip_unknown_etuple_data() {
+        assert_round_trip(
+            "etuple<=255(a:fixed(1))",
+            Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[9, 9])]),
+            "{\"\":\"0909\",\"a\":\"01\"}"
+        );
+        assert_round_trip(
+            "etuple<=255(fixed(1))",
+            Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[9])]),
+            "[\"01\",\"09\"]"
+        );
+    }
+
+    #[test]
+    fn should_see_through_defaulted_templates() {
+        assert_round_trip(
+            "default(etuple<=255(a:fixed(1)),0x0101)",
+            Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[9])]),
+            "{\"\":\"09\",\"a\":\"01\"}"
+        );
+        let template = Template::from_schema("default(tuple(a:fixed(1)),0x01)").unwrap();
+        assert_eq!(
+            from_str(&template, "{\"a\":\"01\",\"\":\"09\"}").err(),
+            Some(Error::json__from_json__unknown_field_name)
+        );
+        assert_eq!(
+            from_str(&template, "{\"a\":\"01\"}"),
+            Ok(Nest::Nests(&[&Nest::Bytes(&[1])]))
+        );
+    }
+
+    #[test]
+    fn should_fill_defaults() {
+        let template = Template::from_schema("etuple<=255(a:fixed(1),b:fixed(1)=0x05)").unwrap();
+        assert_eq!(from_str(&template, "{\"a\":\"01\"}"), Ok(Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[5])])));
+        let template = Template::from_schema("tuple(a:fixed(1),b:default(dynamic<=255,0x0106))").unwrap();
+        assert_eq!(from_str(&template, "{\"a\":\"01\"}"), Ok(Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[6])])));
+        assert_eq!(from_str(&template, "{\"b\":\"01\"}").err(), Some(Error::json__from_json__missing_field));
+    }
+
+    #[test]
+    fn should_error_when_json_does_not_match() {
+        let template = Template::from_schema("tuple(a:fixed(1),b:dlist<=255(fixed(1)))").unwrap();
+        assert_eq!(from_str(&template, "{").err(), Some(Error::json__from_str__invalid_json));
+        assert_eq!(from_str(&template, "[]").err(), Some(Error::json__from_json__should_be_object));
+        assert_eq!(from_str(&template, "{\"a\":\"0\",\"b\":[]}").err(), Some(Error::json__from_json__should_be_hex));
+        assert_eq!(from_str(&template, "{\"a\":1,\"b\":[]}").err(), Some(Error::json__from_json__should_be_hex));
+        assert_eq!(from_str(&template, "{\"a\":\"01\",\"b\":\"\"}").err(), Some(Error::json__from_json__should_be_array));
+        assert_eq!(
+            from_str(&template, "{\"a\":\"01\",\"b\":[],\"c\":\"\"}").err(),
+            Some(Error::json__from_json__unknown_field_name)
+        );
+        assert_eq!(
+            from_str(&template, "{\"a\":\"01\",\"b\":[],\"\":\"\"}").err(),
+            Some(Error::json__from_json__unknown_field_name)
+        );
+        let template = Template::from_schema("tuple(fixed(1))").unwrap();
+        assert_eq!(
+            from_str(&template, "[\"01\",\"02\"]").err(),
+            Some(Error::json__from_json__array_length_should_match_fields_length)
+        );
+        assert_eq!(to_json(&template, &Nest::Nests(&[])).err(), Some(Error::json__to_json__nest_should_match_template));
+        assert_eq!(
+            to_json(&template, &Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[2])])).err(),
+            Some(Error::json__to_json__nest_should_match_template)
+        );
+        let template = Template::from_schema("etuple<=255(fixed(1))").unwrap();
+        assert_eq!(
+            to_json(&template, &Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[2]), &Nest::Bytes(&[3])])).err(),
+            Some(Error::json__to_json__nest_should_match_template)
+        );
+        assert_eq!(to_json(&template, &Nest::Bytes(&[])).err(), Some(Error::nest__try_nests__should_be_nests));
+    }
+
+    #[test]
+    fn should_leave_lengths_to_encode() {
+        let template = Template::from_schema("fixed(2)").unwrap();
+        let nest = from_json(&template, &json_string("01")).unwrap();
+        assert_eq!(template.encode(&nest).err(), Some(Error::fixed__encode_to__bytes_length_should_match_self_length));
+    }
+
+    fn json_string(text: &str) -> ::serde_json::Value {
+        ::serde_json::Value::String(text.to_string())
+    }
+
+}
@@ -0,0 +1,266 @@
+use error::Error;
+use template::Template;
+use encoders::dlist::Dlist;
+use encoders::ilist::Ilist;
+use traits::encoder::Encoder;
+use nest::Nest;
+
+pub enum LazyNest<'t, 'a> {
+    Nest(Nest<'a>),
+    List(LazyList<'t, 'a>)
+}
+
+/// A decoded Dlist or Ilist whose children stay encoded until they are
+/// accessed. `bytes` covers exactly the children, after the length encoding
+/// (and, for an Ilist, after the offsets).
+///
+/// Decoding checks the element count and that every child fits in `bytes`,
+/// but a child's contents are only checked when it is accessed, so `get` and
+/// the iterator can still fail on a malformed child.
+pub struct LazyList<'t, 'a> {
+    template: &'t Template,
+    length: usize,
+    bytes: &'a [u8],
+    offsets: Option<(&'t Ilist, &'a [u8])>
+}
+
+pub struct LazyListIter<'t, 'a> {
+    template: &'t Template,
+    remaining: usize,
+    bytes: &'a [u8],
+    offsets: Option<(&'t Ilist, &'a [u8])>,
+    index: usize
+}
+
+impl<'t, 'a> LazyNest<'t, 'a> {
+    pub fn decode_with_remainder(template: &'t Template, bytes: &'a [u8]) -> Result<(LazyNest<'t, 'a>, &'a [u8]), Error> {
+        if let Some(ilist) = template.encoder().as_any().downcast_ref::<Ilist>() {
+            let (length, offsets, nests_bytes) = ilist.decode_header(bytes)?;
+            let nests_length = if length == 0 { 0 } else { ilist.range_at(offsets, length - 1).1 };
+            let (nests_bytes, remainder) = nests_bytes.split_at(nests_length);
+            return Ok((
+                LazyNest::List(LazyList {
+                    template: ilist.template(),
+                    length,
+                    bytes: nests_bytes,
+                    offsets: Some((ilist, offsets))
+                }),
+                remainder
+            ));
+        }
+        match template.encoder().as_any().downcast_ref::<Dlist>() {
+            Some(dlist) => {
+                let length = dlist.decode_length(bytes)?;
+                let consumed = dlist.skip(bytes)?;
+                Ok((
+                    LazyNest::List(LazyList {
+                        template: dlist.template(),
+                        length,
+                        bytes: &bytes[dlist.length_encoding_length()..consumed],
+                        offsets: None
+                    }),
+                    &bytes[consumed..]
+                ))
+            },
+            None => {
+                let (nest, remainder) = template.decode_with_remainder(bytes)?;
+                Ok((LazyNest::Nest(nest), remainder))
+            }
+        }
+    }
+    pub fn list(&self) -> Option<&LazyList<'t, 'a>> {
+        match self {
+            LazyNest::List(list) => Some(list),
+            LazyNest::Nest(_) => None
+        }
+    }
+    pub fn to_nest(&self) -> Result<Nest<'a>, Error> {
+        match self {
+            LazyNest::Nest(nest) => Ok(nest.clone()),
+            LazyNest::List(list) => list.to_nest()
+        }
+    }
+}
+
+impl<'t, 'a> LazyList<'t, 'a> {
+    pub fn len(&self) -> usize {
+        self.length
+    }
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+    pub fn get(&self, index: usize) -> Result<LazyNest<'t, 'a>, Error> {
+        if index >= self.length {
+            return Err(Error::lazy_list__get__index_should_be_lt_length);
+        }
+        match self.offsets {
+            Some((ilist, offsets)) => {
+                let (start, end) = ilist.range_at(offsets, index);
+                decode_exactly(self.template, &self.bytes[start..end])
+            },
+            None => {
+                let offset = self.offset_of(index)?;
+                Ok(LazyNest::decode_with_remainder(self.template, &self.bytes[offset..])?.0)
+            }
+        }
+    }
+    pub fn iter(&self) -> LazyListIter<'t, 'a> {
+        LazyListIter {
+            template: self.template,
+            remaining: self.length,
+            bytes: self.bytes,
+            offsets: self.offsets,
+            index: 0
+        }
+    }
+    /// Decodes every child, producing the same nest `Template::decode` would.
+    pub fn to_nest(&self) -> Result<Nest<'a>, Error> {
+        let mut nests = Vec::new();
+        for lazy_nest in self.iter() {
+            nests.push(lazy_nest?.to_nest()?);
+        }
+        Ok(Nest::OwnedNests(nests))
+    }
+    fn offset_of(&self, index: usize) -> Result<usize, Error> {
+        let bounds = self.template.size_bounds();
+        if bounds.is_constant() {
+            return Ok(bounds.min * index);
+        }
+        let mut offset = 0;
+        for _ in 0..index {
+            offset += self.template.skip(&self.bytes[offset..])?;
+        }
+        Ok(offset)
+    }
+}
+
+impl<'t, 'a> Iterator for LazyListIter<'t, 'a> {
+    type Item = Result<LazyNest<'t, 'a>, Error>;
+    fn next(&mut self) -> Option<Result<LazyNest<'t, 'a>, Error>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let result = match self.offsets {
+            Some((ilist, offsets)) => {
+                let (start, end) = ilist.range_at(offsets, self.index);
+                decode_exactly(self.template, &self.bytes[start..end]).map(|lazy_nest| (lazy_nest, self.bytes))
+            },
+            None => LazyNest::decode_with_remainder(self.template, self.bytes)
+        };
+        self.index += 1;
+        match result {
+            Ok((lazy_nest, remainder)) => {
+                self.bytes = remainder;
+                Some(Ok(lazy_nest))
+            },
+            Err(error) => {
+                self.remaining = 0;
+                Some(Err(error))
+            }
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+fn decode_exactly<'t, 'a>(template: &'t Template, bytes: &'a [u8]) -> Result<LazyNest<'t, 'a>, Error> {
+    let (lazy_nest, remainder) = LazyNest::decode_with_remainder(template, bytes)?;
+    if !remainder.is_empty() {
+        Err(Error::ilist__decode_with_remainder__nest_length_should_match_offsets)
+    } else {
+        Ok(lazy_nest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template_ids::TemplateId;
+    use error::Error;
+    use nest::Nest;
+    use fixtures::{fixed, dynamic, dlist, ilist};
+
+    #[test]
+    fn should_get_fixed_children() {
+        let template = dlist(TemplateId::DlistGamma, fixed(2));
+        let encoding = template.encode(&Nest::Nests(&[
+            &Nest::Bytes(&[1, 2]),
+            &Nest::Bytes(&[3, 4]),
+            &Nest::Bytes(&[5, 6])
+        ])).unwrap();
+        let lazy_nest = template.decode_lazy(&encoding).unwrap();
+        let list = lazy_nest.list().unwrap();
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.get(2).unwrap().to_nest(), Ok(Nest::Bytes(&[5, 6])));
+        assert_eq!(list.get(0).unwrap().to_nest(), Ok(Nest::Bytes(&[1, 2])));
+        assert_eq!(list.get(3).err(), Some(Error::lazy_list__get__index_should_be_lt_length));
+    }
+
+    #[test]
+    fn should_get_nested_dynamic_children() {
+        let template = dlist(
+            TemplateId::DlistAlpha,
+            dlist(TemplateId::DlistAlpha, dynamic(TemplateId::DynamicAlpha))
+        );
+        let nest = Nest::Nests(&[
+            &Nest::Nests(&[&Nest::Bytes(&[1])]),
+            &Nest::Nests(&[]),
+            &Nest::Nests(&[&Nest::Bytes(&[2, 3]), &Nest::Bytes(&[])])
+        ]);
+        let encoding = template.encode(&nest).unwrap();
+        let lazy_nest = template.decode_lazy(&encoding).unwrap();
+        let child = lazy_nest.list().unwrap().get(2).unwrap();
+        assert_eq!(child.list().unwrap().len(), 2);
+        assert_eq!(child.list().unwrap().get(0).unwrap().to_nest(), Ok(Nest::Bytes(&[2, 3])));
+        assert_eq!(lazy_nest.to_nest(), Ok(nest));
+        assert_eq!(lazy_nest.list().unwrap().iter().count(), 3);
+    }
+
+    #[test]
+    fn should_validate_bounds_when_decoding() {
+        let template = dlist(TemplateId::DlistAlpha, fixed(2));
+        assert_eq!(
+            template.decode_lazy(&[3, 1, 2, 3, 4]).err(),
+            Some(Error::fixed__decode_with_remainder__bytes_length_should_be_gte_self_length)
+        );
+        assert_eq!(
+            template.decode_lazy(&[1, 1, 2, 3]).err(),
+            Some(Error::template__decode__should_not_have_any_remainder)
+        );
+    }
+
+    #[test]
+    fn should_check_child_contents_on_access() {
+        let template = dlist(
+            TemplateId::DlistAlpha,
+            ilist(TemplateId::IlistAlpha, dynamic(TemplateId::DynamicAlpha))
+        );
+        let encoding = [1, 1, 2, 0, 0, 0, 5, 9];
+        let lazy_nest = template.decode_lazy(&encoding).unwrap();
+        let child = lazy_nest.list().unwrap().get(0).unwrap();
+        let error = Some(Error::dynamic__decode_with_remainder__bytes_length_should_be_gte_length_encoding_length_plus_length);
+        assert_eq!(child.list().unwrap().get(0).err(), error);
+        assert_eq!(lazy_nest.to_nest().err(), error);
+        assert_eq!(template.decode(&encoding).err(), error);
+    }
+
+    #[test]
+    fn should_get_ilist_children_by_offset() {
+        let template = ilist(TemplateId::IlistAlpha, dynamic(TemplateId::DynamicAlpha));
+        let nest = Nest::Nests(&[
+            &Nest::Bytes(&[1]),
+            &Nest::Bytes(&[2, 3]),
+            &Nest::Bytes(&[])
+        ]);
+        let encoding = template.encode(&nest).unwrap();
+        let lazy_nest = template.decode_lazy(&encoding).unwrap();
+        let list = lazy_nest.list().unwrap();
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.get(1).unwrap().to_nest(), Ok(Nest::Bytes(&[2, 3])));
+        assert_eq!(list.get(2).unwrap().to_nest(), Ok(Nest::Bytes(&[])));
+        assert_eq!(lazy_nest.to_nest(), Ok(nest));
+    }
+
+}
@@ -0,0 +1,38 @@
+use error::Error;
+use byteorder::{LittleEndian, WriteBytesExt, ReadBytesExt};
+use std::io::{Cursor, Read, Write};
+
+/// Appends the first `length_encoding_length` bytes of `length` as a
+/// little-endian number.
+pub fn encode_length_to(length: usize, length_encoding_length: usize, to: &mut Vec<u8>) {
+    if length > u32::MAX as usize {
+        panic!();
+    }
+    let mut length_encoding = Vec::with_capacity(4);
+    length_encoding.write_u32::<LittleEndian>(length as u32).unwrap();
+    to.extend_from_slice(&length_encoding[..length_encoding_length]);
+}
+
+pub fn write_length_to(length: usize, length_encoding_length: usize, to: &mut dyn Write) -> Result<(), Error> {
+    let mut length_encoding = Vec::with_capacity(length_encoding_length);
+    encode_length_to(length, length_encoding_length, &mut length_encoding);
+    to.write_all(&length_encoding).map_err(|error| Error::encoder__write_to__io_error(error.kind()))
+}
+
+/// Reads the length at the start of `bytes`, returning `error` when `bytes`
+/// is shorter than the length encoding.
+pub fn decode_length(bytes: &[u8], length_encoding_length: usize, error: Error) -> Result<usize, Error> {
+    if bytes.len() < length_encoding_length {
+        Err(error)
+    } else {
+        let mut length_encoding = [0; 4];
+        length_encoding[..length_encoding_length].copy_from_slice(&bytes[..length_encoding_length]);
+        Ok(Cursor::new(&length_encoding).read_u32::<LittleEndian>().unwrap() as usize)
+    }
+}
+
+pub fn read_length_from(from: &mut dyn Read, length_encoding_length: usize) -> Result<usize, Error> {
+    let mut length_encoding = [0; 4];
+    from.read_exact(&mut length_encoding[..length_encoding_length]).map_err(Error::from_read_error)?;
+    Ok(Cursor::new(&length_encoding).read_u32::<LittleEndian>().unwrap() as usize)
+}
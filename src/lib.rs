@@ -3,11 +3,44 @@ extern crate enum_primitive_derive;
 extern crate num_traits;
 
 extern crate byteorder;
+extern crate sha2;
+#[cfg(feature = "arbitrary")]
+extern crate rand;
+#[cfg(feature = "json")]
+extern crate serde_json;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+#[macro_use]
+extern crate serde_derive;
 
 pub mod error;
 pub mod traits;
 pub mod template;
 pub mod template_ids;
+pub mod template_kind;
 pub mod encoders;
 pub mod nest;
+pub mod nest_path;
+pub mod schema;
+pub mod lazy;
+pub mod patch;
+pub mod visit;
+pub mod size_bounds;
+pub mod length_prefix;
+pub mod envelope;
+pub mod compat;
+pub mod migrate;
+#[cfg(feature = "arbitrary")]
+pub mod arbitrary;
+#[cfg(feature = "arbitrary")]
+pub mod conformance;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "json")]
+pub mod vectors;
+#[cfg(feature = "serde")]
+pub mod serialize;
+#[cfg(test)]
+mod fixtures;
 // mod Dynamic;
@@ -0,0 +1,164 @@
+//! Moves values from one template version to the next with a declarative
+//! `Mapping`, instead of bespoke code for every change.
+//!
+//! Mappings work on nests, so anything that leaves the bytes of a value
+//! alone, such as widening a Dynamic or list tier, is just `Mapping::Keep`.
+//! The result is checked when it is encoded with the new template.
+
+use error::Error;
+use template::Template;
+use template_kind::TemplateKind;
+use nest::Nest;
+
+pub enum Mapping {
+    /// Keeps the value as it is.
+    Keep,
+    /// Builds a tuple from the fields of the old tuple or etuple, in the new
+    /// order. Old fields that no source refers to are dropped, as is any
+    /// unknown data an etuple kept.
+    Tuple(Vec<FieldSource>),
+    /// Maps every element of a Dlist or Ilist, or the value of an Optional
+    /// when it is present.
+    Each(Box<Mapping>)
+}
+
+pub enum FieldSource {
+    /// The old field at this index.
+    Index(usize, Mapping),
+    /// The old field with this name.
+    Named(String, Mapping),
+    /// A field the old template does not have.
+    Default(Nest<'static>)
+}
+
+/// Decodes `encoding` with `from`, applies `mapping` and encodes the result
+/// with `to`.
+pub fn transcode(from: &Template, to: &Template, mapping: &Mapping, encoding: &[u8]) -> Result<Vec<u8>, Error> {
+    let nest = from.decode(encoding)?;
+    to.encode(&apply(from, &nest, mapping)?)
+}
+
+/// Applies `mapping` to `nest`, a value of `from`.
+pub fn apply<'a>(from: &Template, nest: &Nest<'a>, mapping: &Mapping) -> Result<Nest<'a>, Error> {
+    match (mapping, from.kind()) {
+        (Mapping::Keep, _) => Ok(nest.clone()),
+        (Mapping::Each(mapping), TemplateKind::Dlist { template, .. })
+        | (Mapping::Each(mapping), TemplateKind::Ilist { template, .. })
+        | (Mapping::Each(mapping), TemplateKind::Optional { template }) => {
+            let mut nests = Vec::with_capacity(nest.try_nests()?.len());
+            for child in nest.iter_nests() {
+                nests.push(apply(template, child, mapping)?);
+            }
+            Ok(Nest::OwnedNests(nests))
+        },
+        (Mapping::Tuple(sources), TemplateKind::Tuple { fields })
+        | (Mapping::Tuple(sources), TemplateKind::Etuple { fields, .. }) => {
+            let children = nest.try_nests()?.collect::<Vec<_>>();
+            if children.len() < fields.len() {
+                return Err(Error::migrate__apply__nest_should_match_template);
+            }
+            let mut nests = Vec::with_capacity(sources.len());
+            for source in sources {
+                let (index, mapping) = match source {
+                    FieldSource::Default(nest) => {
+                        nests.push(nest.clone());
+                        continue;
+                    },
+                    FieldSource::Index(index, mapping) => {
+                        if *index >= fields.len() {
+                            return Err(Error::migrate__apply__index_should_be_lt_length);
+                        }
+                        (*index, mapping)
+                    },
+                    FieldSource::Named(name, mapping) => {
+                        let index = fields.iter().position(|field| field.name.as_ref() == Some(name))
+                            .ok_or(Error::migrate__apply__unknown_field_name)?;
+                        (index, mapping)
+                    }
+                };
+                nests.push(apply(&fields[index].template, children[index], mapping)?);
+            }
+            Ok(Nest::OwnedNests(nests))
+        },
+        _ => Err(Error::migrate__apply__mapping_should_match_template)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template::Template;
+    use migrate::{transcode, apply, Mapping, FieldSource};
+    use error::Error;
+    use nest::Nest;
+
+    #[test]
+    fn should_reorder_drop_and_add_fields() {
+        let v1 = Template::from_schema("tuple(id:fixed(1),legacy:fixed(1),body:dynamic<=255)").unwrap();
+        let v2 = Template::from_schema("tuple(body:dynamic<=65535,id:fixed(1),flags:fixed(1))").unwrap();
+        let mapping = Mapping::Tuple(vec![
+            FieldSource::Named("body".to_string(), Mapping::Keep),
+            FieldSource::Index(0, Mapping::Keep),
+            FieldSource::Default(Nest::OwnedBytes(vec![0]))
+        ]);
+        let encoding = v1.encode(&Nest::Nests(&[
+            &Nest::Bytes(&[7]),
+            &Nest::Bytes(&[8]),
+            &Nest::Bytes(&[1, 2])
+        ])).unwrap();
+        assert_eq!(transcode(&v1, &v2, &mapping, &encoding), Ok(vec![2, 0, 1, 2, 7, 0]));
+    }
+
+    #[test]
+    fn should_drop_unknown_etuple_data() {
+        let v1 = Template::from_schema("etuple<=255(a:fixed(1))").unwrap();
+        let v2 = Template::from_schema("tuple(a:fixed(1))").unwrap();
+        let mapping = Mapping::Tuple(vec![FieldSource::Named("a".to_string(), Mapping::Keep)]);
+        assert_eq!(transcode(&v1, &v2, &mapping, &[3, 1, 2, 3]), Ok(vec![1]));
+    }
+
+    #[test]
+    fn should_map_list_elements() {
+        let v1 = Template::from_schema("dlist<=255(tuple(a:fixed(1),b:fixed(1)))").unwrap();
+        let v2 = Template::from_schema("ilist<=255(tuple(b:fixed(1)))").unwrap();
+        let mapping = Mapping::Each(Box::new(Mapping::Tuple(vec![
+            FieldSource::Named("b".to_string(), Mapping::Keep)
+        ])));
+        assert_eq!(transcode(&v1, &v2, &mapping, &[2, 1, 2, 3, 4]), Ok(vec![2, 1, 0, 0, 0, 2, 0, 0, 0, 2, 4]));
+    }
+
+    #[test]
+    fn should_map_optional_values() {
+        let v1 = Template::from_schema("optional(tuple(a:fixed(1),b:fixed(1)))").unwrap();
+        let v2 = Template::from_schema("optional(tuple(b:fixed(1)))").unwrap();
+        let mapping = Mapping::Each(Box::new(Mapping::Tuple(vec![
+            FieldSource::Named("b".to_string(), Mapping::Keep)
+        ])));
+        assert_eq!(transcode(&v1, &v2, &mapping, &[1, 1, 2]), Ok(vec![1, 2]));
+        assert_eq!(transcode(&v1, &v2, &mapping, &[0]), Ok(vec![0]));
+    }
+
+    #[test]
+    fn should_error_when_mapping_does_not_match() {
+        let v1 = Template::from_schema("tuple(a:fixed(1))").unwrap();
+        let nest = Nest::Nests(&[&Nest::Bytes(&[1])]);
+        assert_eq!(
+            apply(&v1, &nest, &Mapping::Each(Box::new(Mapping::Keep))).err(),
+            Some(Error::migrate__apply__mapping_should_match_template)
+        );
+        assert_eq!(
+            apply(&v1, &nest, &Mapping::Tuple(vec![FieldSource::Index(1, Mapping::Keep)])).err(),
+            Some(Error::migrate__apply__index_should_be_lt_length)
+        );
+        assert_eq!(
+            apply(&v1, &nest, &Mapping::Tuple(vec![FieldSource::Named("b".to_string(), Mapping::Keep)])).err(),
+            Some(Error::migrate__apply__unknown_field_name)
+        );
+        let v2 = Template::from_schema("tuple(fixed(2))").unwrap();
+        assert_eq!(
+            transcode(&v1, &v2, &Mapping::Keep, &[1]).err(),
+            Some(Error::fixed__encode_to__bytes_length_should_match_self_length)
+        );
+    }
+
+}
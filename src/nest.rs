@@ -1,34 +1,138 @@
-#[derive(Debug)]
+use std::slice;
+use error::Error;
+use nest_path::NestPath;
+
+#[derive(Debug, Clone)]
 pub enum Nest<'a> {
     Bytes(&'a [u8]),
-    Nests(&'a [&'a Nest<'a>])
+    Nests(&'a [&'a Nest<'a>]),
+    OwnedBytes(Vec<u8>),
+    OwnedNests(Vec<Nest<'a>>)
+}
+
+pub enum NestsIter<'b, 'a: 'b> {
+    Borrowed(slice::Iter<'b, &'a Nest<'a>>),
+    Owned(slice::Iter<'b, Nest<'a>>)
+}
+
+impl<'b, 'a> Iterator for NestsIter<'b, 'a> {
+    type Item = &'b Nest<'a>;
+    fn next(&mut self) -> Option<&'b Nest<'a>> {
+        match self {
+            NestsIter::Borrowed(iter) => iter.next().copied(),
+            NestsIter::Owned(iter) => iter.next()
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            NestsIter::Borrowed(iter) => iter.size_hint(),
+            NestsIter::Owned(iter) => iter.size_hint()
+        }
+    }
+}
+
+impl<'b, 'a> ExactSizeIterator for NestsIter<'b, 'a> {}
+
+pub struct Walk<'b, 'a: 'b> {
+    stack: Vec<(NestPath, &'b Nest<'a>)>
+}
+
+impl<'b, 'a> Iterator for Walk<'b, 'a> {
+    type Item = (NestPath, &'b Nest<'a>);
+    fn next(&mut self) -> Option<(NestPath, &'b Nest<'a>)> {
+        let (path, nest) = self.stack.pop()?;
+        if !nest.is_bytes() {
+            let children = nest.iter_nests().enumerate().map(|(index, child)| (path.child(index), child));
+            let start = self.stack.len();
+            self.stack.extend(children);
+            self.stack[start..].reverse();
+        }
+        Some((path, nest))
+    }
 }
 
 impl<'a> Nest<'a> {
     pub fn bytes(&self) -> &[u8]  {
-        match (&self) {
-            Nest::Bytes(bytes) => &bytes,
+        match self {
+            Nest::Bytes(bytes) => bytes,
+            Nest::OwnedBytes(bytes) => bytes,
             _ => panic!()
         }
     }
-    pub fn nests(&self) -> &[&Nest]  {
-        match (&self) {
+    pub fn nests(&self) -> &[&Nest<'a>]  {
+        match self {
             Nest::Nests(nests) => nests,
             _ => panic!()
         }
     }
+    /// The children of either a borrowed or an owned nest.
+    pub fn iter_nests<'b>(&'b self) -> NestsIter<'b, 'a>  {
+        match self {
+            Nest::Nests(nests) => NestsIter::Borrowed(nests.iter()),
+            Nest::OwnedNests(nests) => NestsIter::Owned(nests.iter()),
+            _ => panic!()
+        }
+    }
+    pub fn try_bytes(&self) -> Result<&[u8], Error> {
+        if self.is_bytes() {
+            Ok(self.bytes())
+        } else {
+            Err(Error::nest__try_bytes__should_be_bytes)
+        }
+    }
+    pub fn try_nests<'b>(&'b self) -> Result<NestsIter<'b, 'a>, Error> {
+        if self.is_bytes() {
+            Err(Error::nest__try_nests__should_be_nests)
+        } else {
+            Ok(self.iter_nests())
+        }
+    }
+    pub fn child(&self, index: usize) -> Result<&Nest<'a>, Error> {
+        let child = match self {
+            Nest::Nests(nests) => nests.get(index).copied(),
+            Nest::OwnedNests(nests) => nests.get(index),
+            _ => return Err(Error::nest__try_nests__should_be_nests)
+        };
+        child.ok_or(Error::nest__child__index_should_be_lt_length)
+    }
+    pub fn get(&self, path: &[usize]) -> Result<&Nest<'a>, Error> {
+        let mut nest = self;
+        for index in path {
+            nest = nest.child(*index)?;
+        }
+        Ok(nest)
+    }
+    /// Every nest under and including this one, depth first, with its path.
+    pub fn walk<'b>(&'b self) -> Walk<'b, 'a> {
+        Walk {
+            stack: vec![(NestPath::root(), self)]
+        }
+    }
+    /// The bytes of every leaf, in encoding order, with its path.
+    pub fn leaves<'b>(&'b self) -> impl Iterator<Item = (NestPath, &'b [u8])> + 'b {
+        self.walk().filter(|(_, nest)| nest.is_bytes()).map(|(path, nest)| (path, nest.bytes()))
+    }
+    pub fn to_owned_nest(&self) -> Nest<'static> {
+        if self.is_bytes() {
+            Nest::OwnedBytes(self.bytes().to_vec())
+        } else {
+            Nest::OwnedNests(self.iter_nests().map(|nest| nest.to_owned_nest()).collect())
+        }
+    }
+    pub fn is_bytes(&self) -> bool {
+        matches!(self, Nest::Bytes(_) | Nest::OwnedBytes(_))
+    }
 }
 
 impl<'a, 'b> PartialEq<Nest<'a>> for Nest<'b> {
     fn eq(&self, other: &Nest) -> bool {
-        match (&self, other) {
-            (&Nest::Bytes(self_bytes), &Nest::Bytes(other_bytes)) => {
-                self_bytes.iter().zip(other_bytes.iter()).all(|(a,b)| a == b)
-            },
-            (&Nest::Nests(self_nests), &Nest::Nests(other_nests)) => {
-                self_nests.iter().zip(other_nests.iter()).all(|(a,b)| a == b)
-            },
-            (_, _) => false,
+        if self.is_bytes() != other.is_bytes() {
+            false
+        } else if self.is_bytes() {
+            self.bytes() == other.bytes()
+        } else {
+            self.iter_nests().len() == other.iter_nests().len()
+                && self.iter_nests().zip(other.iter_nests()).all(|(a, b)| a == b)
         }
     }
 }
@@ -36,6 +140,8 @@ impl<'a, 'b> PartialEq<Nest<'a>> for Nest<'b> {
 #[cfg(test)]
 mod tests {
     use super::Nest;
+    use error::Error;
+    use nest_path::NestPath;
 
     #[test]
     fn equal_bytes_should_be_equal() {
@@ -93,4 +199,75 @@ mod tests {
         );
     }
 
+    #[test]
+    fn owned_and_borrowed_should_be_equal() {
+        assert_eq!(
+            Nest::OwnedNests(vec![
+                Nest::OwnedBytes(vec![1, 2]),
+                Nest::Bytes(&[3])
+            ]),
+            Nest::Nests(&[
+                &Nest::Bytes(&[1, 2]),
+                &Nest::OwnedBytes(vec![3])
+            ])
+        );
+    }
+
+    #[test]
+    fn different_lengths_should_not_be_equal() {
+        assert_ne!(
+            Nest::Bytes(&[1, 2, 3]),
+            Nest::Bytes(&[1, 2])
+        );
+        assert_ne!(
+            Nest::Nests(&[&Nest::Bytes(&[1])]),
+            Nest::OwnedNests(vec![Nest::Bytes(&[1]), Nest::Bytes(&[2])])
+        );
+    }
+
+    #[test]
+    fn should_get_by_path() {
+        let nest = Nest::Nests(&[
+            &Nest::Bytes(&[1]),
+            &Nest::Nests(&[
+                &Nest::Bytes(&[2]),
+                &Nest::Bytes(&[3])
+            ])
+        ]);
+        assert_eq!(nest.get(&NestPath::from(vec![1, 1])), Ok(&Nest::Bytes(&[3])));
+        assert_eq!(nest.get(&[]).map(|root| root.nests().len()), Ok(2));
+        assert_eq!(nest.get(&[2]).err(), Some(Error::nest__child__index_should_be_lt_length));
+        assert_eq!(nest.get(&[0, 0]).err(), Some(Error::nest__try_nests__should_be_nests));
+        assert_eq!(nest.try_bytes().err(), Some(Error::nest__try_bytes__should_be_bytes));
+    }
+
+    #[test]
+    fn should_walk_paths_and_leaves() {
+        let nest = Nest::OwnedNests(vec![
+            Nest::Bytes(&[1]),
+            Nest::OwnedNests(vec![
+                Nest::Bytes(&[2]),
+                Nest::OwnedNests(vec![])
+            ]),
+            Nest::Bytes(&[3])
+        ]);
+        let paths: Vec<String> = nest.walk().map(|(path, _)| path.to_string()).collect();
+        assert_eq!(paths, vec!["[]", "[0]", "[1]", "[1][0]", "[1][1]", "[2]"]);
+        let leaves: Vec<(NestPath, &[u8])> = nest.leaves().collect();
+        assert_eq!(leaves, vec![
+            (NestPath::from(vec![0]), &[1][..]),
+            (NestPath::from(vec![1, 0]), &[2][..]),
+            (NestPath::from(vec![2]), &[3][..])
+        ]);
+    }
+
+    #[test]
+    fn should_index_borrowed_nests_and_iterate_owned_nests() {
+        let borrowed = Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[2])]);
+        assert_eq!(borrowed.nests()[1].bytes(), &[2]);
+        let owned = Nest::OwnedNests(vec![Nest::Bytes(&[1]), Nest::OwnedBytes(vec![2])]);
+        assert_eq!(owned.iter_nests().nth(1), Some(&Nest::Bytes(&[2])));
+        assert_eq!(owned.iter_nests().len(), borrowed.iter_nests().len());
+    }
+
 }
@@ -0,0 +1,86 @@
+use std::fmt;
+use std::ops::Deref;
+
+/// The child indices leading from a root nest to one of its descendants.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct NestPath(Vec<usize>);
+
+impl NestPath {
+    pub fn root() -> NestPath {
+        NestPath(vec![])
+    }
+    pub fn child(&self, index: usize) -> NestPath {
+        let mut indices = self.0.clone();
+        indices.push(index);
+        NestPath(indices)
+    }
+    pub fn parent(&self) -> Option<NestPath> {
+        if self.0.is_empty() {
+            None
+        } else {
+            Some(NestPath(self.0[..self.0.len() - 1].to_vec()))
+        }
+    }
+    pub fn indices(&self) -> &[usize] {
+        &self.0
+    }
+    pub fn push(&mut self, index: usize) {
+        self.0.push(index);
+    }
+    pub fn pop(&mut self) -> Option<usize> {
+        self.0.pop()
+    }
+}
+
+impl Deref for NestPath {
+    type Target = [usize];
+    fn deref(&self) -> &[usize] {
+        &self.0
+    }
+}
+
+impl From<Vec<usize>> for NestPath {
+    fn from(indices: Vec<usize>) -> NestPath {
+        NestPath(indices)
+    }
+}
+
+impl<'a> From<&'a [usize]> for NestPath {
+    fn from(indices: &'a [usize]) -> NestPath {
+        NestPath(indices.to_vec())
+    }
+}
+
+impl fmt::Display for NestPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "[]");
+        }
+        for index in &self.0 {
+            write!(f, "[{}]", index)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use nest_path::NestPath;
+
+    #[test]
+    fn should_build_paths() {
+        let path = NestPath::root().child(1).child(0);
+        assert_eq!(path, NestPath::from(vec![1, 0]));
+        assert_eq!(path.parent(), Some(NestPath::from(vec![1])));
+        assert_eq!(NestPath::root().parent(), None);
+        assert_eq!(path.len(), 2);
+    }
+
+    #[test]
+    fn should_display() {
+        assert_eq!(NestPath::from(vec![1, 0]).to_string(), "[1][0]");
+        assert_eq!(NestPath::root().to_string(), "[]");
+    }
+
+}
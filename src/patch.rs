@@ -0,0 +1,192 @@
+use error::Error;
+use byteorder::{LittleEndian, WriteBytesExt, ReadBytesExt};
+use std::io::Cursor;
+
+/// A little-endian number inside an encoding that covers the value being
+/// patched, such as an Ilist offset, and so must change with its length.
+pub struct LengthField {
+    pub position: usize,
+    pub length_encoding_length: usize,
+    pub max_length: usize
+}
+
+impl LengthField {
+    fn read(&self, encoding: &[u8]) -> usize {
+        let mut length_encoding = [0; 4];
+        length_encoding[..self.length_encoding_length]
+            .copy_from_slice(&encoding[self.position..self.position + self.length_encoding_length]);
+        Cursor::new(&length_encoding).read_u32::<LittleEndian>().unwrap() as usize
+    }
+    fn write(&self, encoding: &mut [u8], length: usize) {
+        let mut length_encoding = Vec::with_capacity(4);
+        length_encoding.write_u32::<LittleEndian>(length as u32).unwrap();
+        encoding[self.position..self.position + self.length_encoding_length]
+            .copy_from_slice(&length_encoding[..self.length_encoding_length]);
+    }
+}
+
+/// Replaces `old_length` bytes at `start` with `replacement` and moves every
+/// length field by the difference. Nothing is written unless every length
+/// field can hold its new value.
+pub fn splice(
+    encoding: &mut Vec<u8>,
+    start: usize,
+    old_length: usize,
+    replacement: &[u8],
+    length_fields: &[LengthField]
+) -> Result<(), Error> {
+    if replacement.len() == old_length {
+        encoding[start..start + old_length].copy_from_slice(replacement);
+        return Ok(());
+    }
+    let mut lengths = Vec::with_capacity(length_fields.len());
+    for length_field in length_fields {
+        let length = (length_field.read(encoding) + replacement.len()).checked_sub(old_length)
+            .ok_or(Error::template__patch__length_field_should_cover_value)?;
+        if length > length_field.max_length {
+            return Err(Error::template__patch__length_should_be_lte_max_length);
+        }
+        lengths.push(length);
+    }
+    encoding.splice(start..start + old_length, replacement.iter().cloned());
+    for (length_field, length) in length_fields.iter().zip(lengths) {
+        length_field.write(encoding, length);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template_ids::TemplateId;
+    use template::Template;
+    use error::Error;
+    use nest::Nest;
+    use fixtures::{fixed, dynamic, dlist, ilist};
+
+    #[test]
+    fn should_overwrite_fixed_in_place() {
+        let template = dlist(TemplateId::DlistAlpha, dlist(TemplateId::DlistAlpha, fixed(2)));
+        let mut encoding = template.encode(&Nest::Nests(&[
+            &Nest::Nests(&[&Nest::Bytes(&[1, 1])]),
+            &Nest::Nests(&[&Nest::Bytes(&[2, 2]), &Nest::Bytes(&[3, 3])])
+        ])).unwrap();
+        assert_eq!(template.patch(&mut encoding, &[1, 1], &Nest::Bytes(&[9, 9])), Ok(()));
+        assert_eq!(encoding, vec![2, 1, 1, 1, 2, 2, 2, 9, 9]);
+    }
+
+    #[test]
+    fn should_resize_dynamic() {
+        let template = dlist(TemplateId::DlistAlpha, dynamic(TemplateId::DynamicAlpha));
+        let mut encoding = template.encode(&Nest::Nests(&[
+            &Nest::Bytes(&[1]),
+            &Nest::Bytes(&[2, 2])
+        ])).unwrap();
+        assert_eq!(template.patch(&mut encoding, &[0], &Nest::Bytes(&[7, 7, 7])), Ok(()));
+        assert_eq!(
+            template.decode(&encoding),
+            Ok(Nest::Nests(&[&Nest::Bytes(&[7, 7, 7]), &Nest::Bytes(&[2, 2])]))
+        );
+    }
+
+    #[test]
+    fn should_adjust_ilist_offsets() {
+        let template = ilist(TemplateId::IlistAlpha, dlist(TemplateId::DlistAlpha, dynamic(TemplateId::DynamicAlpha)));
+        let mut encoding = template.encode(&Nest::Nests(&[
+            &Nest::Nests(&[&Nest::Bytes(&[1])]),
+            &Nest::Nests(&[&Nest::Bytes(&[2, 2]), &Nest::Bytes(&[3])]),
+            &Nest::Nests(&[])
+        ])).unwrap();
+        assert_eq!(template.patch(&mut encoding, &[1, 0], &Nest::Bytes(&[])), Ok(()));
+        assert_eq!(
+            template.decode(&encoding),
+            Ok(Nest::Nests(&[
+                &Nest::Nests(&[&Nest::Bytes(&[1])]),
+                &Nest::Nests(&[&Nest::Bytes(&[]), &Nest::Bytes(&[3])]),
+                &Nest::Nests(&[])
+            ]))
+        );
+        assert_eq!(template.patch(&mut encoding, &[0], &Nest::Nests(&[])), Ok(()));
+        assert_eq!(
+            template.decode(&encoding),
+            Ok(Nest::Nests(&[
+                &Nest::Nests(&[]),
+                &Nest::Nests(&[&Nest::Bytes(&[]), &Nest::Bytes(&[3])]),
+                &Nest::Nests(&[])
+            ]))
+        );
+    }
+
+    #[test]
+    fn should_not_write_when_patch_fails() {
+        let template = ilist(TemplateId::IlistAlpha, dynamic(TemplateId::DynamicAlpha));
+        let mut encoding = template.encode(&Nest::Nests(&[
+            &Nest::Bytes(&[0; 200]),
+            &Nest::Bytes(&[1])
+        ])).unwrap();
+        let original = encoding.clone();
+        assert_eq!(
+            template.patch(&mut encoding, &[2], &Nest::Bytes(&[1])),
+            Err(Error::encoder__locate__index_should_be_lt_length)
+        );
+        assert_eq!(
+            template.patch(&mut encoding, &[0, 0], &Nest::Bytes(&[1])),
+            Err(Error::encoder__locate__path_should_end_at_bytes)
+        );
+        assert_eq!(
+            template.patch(&mut encoding, &[0], &Nest::Bytes(&[1; 256])),
+            Err(Error::dynamic__encode_to__bytes_length_should_be_lte_max_length)
+        );
+        assert_eq!(encoding, original);
+        let template = Template::from_schema("etuple<=255(dynamic<=255,dynamic<=255)").unwrap();
+        let mut encoding = template.encode(&Nest::Nests(&[
+            &Nest::Bytes(&[0; 200]),
+            &Nest::Bytes(&[1])
+        ])).unwrap();
+        let original = encoding.clone();
+        assert_eq!(
+            template.patch(&mut encoding, &[1], &Nest::Bytes(&[1; 60])),
+            Err(Error::template__patch__length_should_be_lte_max_length)
+        );
+        assert_eq!(encoding, original);
+    }
+
+    #[test]
+    fn should_error_when_encoding_is_truncated() {
+        let template = dlist(TemplateId::DlistAlpha, fixed(2));
+        let mut encoding = vec![5, 1, 2];
+        assert_eq!(
+            template.patch(&mut encoding, &[4], &Nest::Bytes(&[9, 9])),
+            Err(Error::dlist__locate__bytes_length_should_be_gte_nests_length)
+        );
+        assert_eq!(
+            template.patch(&mut encoding, &[1], &Nest::Bytes(&[9, 9])),
+            Err(Error::dlist__locate__bytes_length_should_be_gte_nests_length)
+        );
+        assert_eq!(template.patch(&mut encoding, &[0], &Nest::Bytes(&[9, 9])), Ok(()));
+        assert_eq!(encoding, vec![5, 9, 9]);
+    }
+
+    #[test]
+    fn should_error_when_ilist_or_etuple_encoding_is_truncated() {
+        let nest = Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[2, 2])]);
+        let templates = vec![
+            ilist(TemplateId::IlistAlpha, dynamic(TemplateId::DynamicAlpha)),
+            Template::from_schema("etuple<=255(dynamic<=255,dynamic<=255)").unwrap()
+        ];
+        for template in templates {
+            let encoding = template.encode(&nest).unwrap();
+            for length in 0..encoding.len() {
+                let mut truncated = encoding[..length].to_vec();
+                assert!(template.patch(&mut truncated, &[1], &Nest::Bytes(&[9])).is_err());
+                assert_eq!(truncated, &encoding[..length]);
+            }
+            let mut length_fields = vec![];
+            assert_eq!(
+                template.encoder().locate(&encoding, encoding.len() + 1, &[1], &mut length_fields).err(),
+                Some(Error::encoder__locate__start_should_be_lte_encoding_length)
+            );
+        }
+    }
+
+}
@@ -0,0 +1,494 @@
+//! A text form of templates, for reading and reviewing schemas:
+//!
+//! ```text
+//! fixed(32)                  Fixed, length 1 to 65792
+//! dynamic<=65535             Dynamic, max length 255, 65535, 16777215 or 4294967295
+//! dlist<=255(fixed(20))      Dlist with the same max lengths as Dynamic
+//! ilist<=255(dynamic<=255)   Ilist with the same max lengths as Dynamic
+//! tuple(id:fixed(8),body"Message body":dynamic<=255)
+//!                            Tuple, 1 to 255 fields
+//! etuple<=255(id:fixed(8),flags:fixed(1)=0x00)
+//!                            Etuple with the same max lengths as Dynamic
+//! default(fixed(2),0x0102)   Any template with an explicit default, as the
+//!                            hex of its encoding
+//! choice(circle:fixed(4),square:dynamic<=255)
+//!                            Choice, 1 to 255 variants
+//! optional(fixed(1))         Optional
+//! ```
+//!
+//! A tuple field or choice variant may be prefixed with a name (letters,
+//! digits and `_`), a quoted doc string (`\"` and `\\` escape), or both,
+//! followed by `:`.
+//! An etuple field may be followed by `=` and its default, as the hex of its
+//! encoding.
+//! Templates nest at most `MAX_DEPTH` levels deep.
+//! Whitespace between tokens is ignored. `print` produces the canonical form
+//! with no whitespace.
+
+use error::Error;
+use template::{Template, MAX_DEPTH};
+use template_ids::TemplateId;
+use template_kind::TemplateKind;
+use nest::Nest;
+use encoders::fixed::Fixed;
+use encoders::dynamic::Dynamic;
+use encoders::dlist::Dlist;
+use encoders::ilist::Ilist;
+use encoders::tuple::{Tuple, Field};
+use encoders::etuple::Etuple;
+use encoders::defaulted::Defaulted;
+use encoders::choice::Choice;
+use encoders::optional::Optional;
+
+const MAX_LENGTHS: [usize; 4] = [255, 65535, 16777215, 4294967295];
+
+pub fn parse(text: &str) -> Result<Template, Error> {
+    let mut parser = Parser { text: text.as_bytes(), position: 0, depth: 0 };
+    let template = parser.template()?;
+    parser.skip_whitespace();
+    if parser.position < parser.text.len() {
+        Err(Error::schema__parse__should_not_have_any_remainder(parser.position))
+    } else {
+        Ok(template)
+    }
+}
+
+pub fn print(template: &Template) -> String {
+    let mut text = String::new();
+    print_to(template, &mut text);
+    text
+}
+
+fn print_hex_to(bytes: &[u8], text: &mut String) {
+    text.push_str("0x");
+    for byte in bytes {
+        text.push_str(&format!("{:02x}", byte));
+    }
+}
+
+fn print_to(template: &Template, text: &mut String) {
+    if let Some(defaulted) = template.encoder().as_any().downcast_ref::<Defaulted>() {
+        text.push_str("default(");
+        print_to(defaulted.template(), text);
+        text.push(',');
+        print_hex_to(&template.encode(&template.default_nest()).unwrap(), text);
+        text.push(')');
+        return;
+    }
+    let kind = template.kind();
+    match kind {
+        TemplateKind::Fixed { length } => {
+            text.push_str(&format!("fixed({})", length));
+        },
+        TemplateKind::Dynamic { max_length, .. } => {
+            text.push_str(&format!("dynamic<={}", max_length));
+        },
+        TemplateKind::Dlist { max_length, template, .. } | TemplateKind::Ilist { max_length, template, .. } => {
+            text.push_str(&format!("{}<={}(", kind.name(), max_length));
+            print_to(template, text);
+            text.push(')');
+        },
+        TemplateKind::Optional { template } => {
+            text.push_str("optional(");
+            print_to(template, text);
+            text.push(')');
+        },
+        TemplateKind::Tuple { fields } | TemplateKind::Etuple { fields, .. } | TemplateKind::Choice { variants: fields } => {
+            match kind {
+                TemplateKind::Etuple { max_length, .. } => text.push_str(&format!("etuple<={}(", max_length)),
+                _ => text.push_str(&format!("{}(", kind.name()))
+            }
+            for (index, field) in fields.iter().enumerate() {
+                if index > 0 {
+                    text.push(',');
+                }
+                if let Some(ref name) = field.name {
+                    text.push_str(name);
+                }
+                if let Some(ref doc) = field.doc {
+                    text.push('"');
+                    for character in doc.chars() {
+                        if character == '"' || character == '\\' {
+                            text.push('\\');
+                        }
+                        text.push(character);
+                    }
+                    text.push('"');
+                }
+                if field.name.is_some() || field.doc.is_some() {
+                    text.push(':');
+                }
+                print_to(&field.template, text);
+                if let Some(ref default) = field.default {
+                    text.push('=');
+                    print_hex_to(&field.template.encode(default).unwrap(), text);
+                }
+            }
+            text.push(')');
+        }
+    }
+}
+
+struct Parser<'s> {
+    text: &'s [u8],
+    position: usize,
+    depth: usize
+}
+
+impl<'s> Parser<'s> {
+    fn skip_whitespace(&mut self) {
+        while self.position < self.text.len() && self.text[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+    }
+    fn expect(&mut self, token: &str) -> Result<(), Error> {
+        self.skip_whitespace();
+        if self.text[self.position..].starts_with(token.as_bytes()) {
+            self.position += token.len();
+            Ok(())
+        } else if self.position >= self.text.len() {
+            Err(Error::schema__parse__unexpected_end)
+        } else {
+            Err(Error::schema__parse__unexpected_character(self.position))
+        }
+    }
+    fn word(&mut self) -> Result<(&'s str, usize), Error> {
+        self.skip_whitespace();
+        let start = self.position;
+        while self.position < self.text.len() && self.text[self.position].is_ascii_alphabetic() {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err(self.unexpected());
+        }
+        Ok((::std::str::from_utf8(&self.text[start..self.position]).unwrap(), start))
+    }
+    fn number(&mut self) -> Result<(usize, usize), Error> {
+        self.skip_whitespace();
+        let start = self.position;
+        while self.position < self.text.len() && self.text[self.position].is_ascii_digit() {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err(self.unexpected());
+        }
+        ::std::str::from_utf8(&self.text[start..self.position]).unwrap()
+            .parse()
+            .map(|number| (number, start))
+            .map_err(|_| Error::schema__parse__invalid_length(start))
+    }
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.text.get(self.position).cloned()
+    }
+    fn identifier(&mut self) -> &'s str {
+        self.skip_whitespace();
+        let start = self.position;
+        while self.position < self.text.len()
+            && (self.text[self.position].is_ascii_alphanumeric() || self.text[self.position] == b'_') {
+            self.position += 1;
+        }
+        ::std::str::from_utf8(&self.text[start..self.position]).unwrap()
+    }
+    fn string(&mut self) -> Result<String, Error> {
+        self.expect("\"")?;
+        let mut bytes = vec![];
+        loop {
+            match self.text.get(self.position) {
+                None => return Err(Error::schema__parse__unexpected_end),
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    self.position += 1;
+                    match self.text.get(self.position) {
+                        None => return Err(Error::schema__parse__unexpected_end),
+                        Some(&byte) if byte == b'"' || byte == b'\\' => bytes.push(byte),
+                        Some(_) => return Err(Error::schema__parse__unexpected_character(self.position))
+                    }
+                },
+                Some(&byte) => bytes.push(byte)
+            }
+            self.position += 1;
+        }
+        self.position += 1;
+        Ok(String::from_utf8(bytes).unwrap())
+    }
+    /// Parses `[name]["doc"]:template[=0xdefault]`, or a bare template with
+    /// an optional default.
+    fn field(&mut self) -> Result<Field, Error> {
+        let start = self.position;
+        let name = self.identifier();
+        let doc = if self.peek() == Some(b'"') { Some(self.string()?) } else { None };
+        let mut field = if self.peek() != Some(b':') {
+            if doc.is_some() {
+                return Err(self.unexpected());
+            }
+            self.position = start;
+            Field::new(self.template()?)
+        } else {
+            self.expect(":")?;
+            Field {
+                template: self.template()?,
+                name: if name.is_empty() { None } else { Some(name.to_string()) },
+                doc,
+                default: None
+            }
+        };
+        if self.peek() == Some(b'=') {
+            self.expect("=")?;
+            field.default = Some(self.default(&field.template)?);
+        }
+        Ok(field)
+    }
+    /// Parses `0x` and the hex of a value encoded with `template`.
+    fn default(&mut self, template: &Template) -> Result<Nest<'static>, Error> {
+        self.expect("0x")?;
+        let position = self.position;
+        let encoding = self.hex()?;
+        template.decode(&encoding)
+            .map(|nest| nest.to_owned_nest())
+            .map_err(|_| Error::schema__parse__invalid_default(position))
+    }
+    fn hex(&mut self) -> Result<Vec<u8>, Error> {
+        let start = self.position;
+        while self.position < self.text.len() && self.text[self.position].is_ascii_hexdigit() {
+            self.position += 1;
+        }
+        if (self.position - start) % 2 == 1 {
+            return Err(self.unexpected());
+        }
+        Ok(self.text[start..self.position].chunks(2).map(|pair| {
+            u8::from_str_radix(::std::str::from_utf8(pair).unwrap(), 16).unwrap()
+        }).collect())
+    }
+    fn fields(&mut self) -> Result<Vec<Field>, Error> {
+        self.expect("(")?;
+        let mut fields = vec![self.field()?];
+        while self.peek() == Some(b',') {
+            self.expect(",")?;
+            fields.push(self.field()?);
+        }
+        self.expect(")")?;
+        Ok(fields)
+    }
+    fn unexpected(&self) -> Error {
+        if self.position >= self.text.len() {
+            Error::schema__parse__unexpected_end
+        } else {
+            Error::schema__parse__unexpected_character(self.position)
+        }
+    }
+    /// Parses `<=max_length` and returns the index of its tier.
+    fn tier(&mut self) -> Result<usize, Error> {
+        self.expect("<=")?;
+        let (max_length, position) = self.number()?;
+        MAX_LENGTHS.iter().position(|tier_max_length| *tier_max_length == max_length)
+            .ok_or(Error::schema__parse__invalid_length(position))
+    }
+    /// Parses a template nested at most `MAX_DEPTH` levels deep, like
+    /// `Template::from_jinyang` accepts.
+    fn template(&mut self) -> Result<Template, Error> {
+        if self.depth > MAX_DEPTH {
+            self.skip_whitespace();
+            return Err(Error::schema__parse__depth_should_be_lte_max_depth(self.position));
+        }
+        self.depth += 1;
+        let template = self.template_at_depth();
+        self.depth -= 1;
+        template
+    }
+    fn template_at_depth(&mut self) -> Result<Template, Error> {
+        let (kind, position) = self.word()?;
+        match kind {
+            "fixed" => {
+                self.expect("(")?;
+                let (length, position) = self.number()?;
+                self.expect(")")?;
+                let template_id = if length > 256 { TemplateId::FixedBeta } else { TemplateId::FixedAlpha };
+                let fixed = Fixed::new(template_id, length)
+                    .map_err(|_| Error::schema__parse__invalid_length(position))?;
+                Ok(Template::new(Box::new(fixed)))
+            },
+            "dynamic" => {
+                let tier = self.tier()?;
+                let template_id = [
+                    TemplateId::DynamicAlpha,
+                    TemplateId::DynamicBeta,
+                    TemplateId::DynamicGamma,
+                    TemplateId::DynamicDelta
+                ][tier];
+                Ok(Template::new(Box::new(Dynamic::new(template_id)?)))
+            },
+            "dlist" | "ilist" => {
+                let tier = self.tier()?;
+                self.expect("(")?;
+                let template = self.template()?;
+                self.expect(")")?;
+                if kind == "dlist" {
+                    let template_id = [
+                        TemplateId::DlistAlpha,
+                        TemplateId::DlistBeta,
+                        TemplateId::DlistGamma,
+                        TemplateId::DlistDelta
+                    ][tier];
+                    Ok(Template::new(Box::new(Dlist::new(template_id, template)?)))
+                } else {
+                    let template_id = [
+                        TemplateId::IlistAlpha,
+                        TemplateId::IlistBeta,
+                        TemplateId::IlistGamma,
+                        TemplateId::IlistDelta
+                    ][tier];
+                    Ok(Template::new(Box::new(Ilist::new(template_id, template)?)))
+                }
+            },
+            "tuple" => {
+                let fields = self.fields()?;
+                Ok(Template::new(Box::new(Tuple::new(fields)?)))
+            },
+            "etuple" => {
+                let tier = self.tier()?;
+                let template_id = [
+                    TemplateId::EtupleAlpha,
+                    TemplateId::EtupleBeta,
+                    TemplateId::EtupleGamma,
+                    TemplateId::EtupleDelta
+                ][tier];
+                let fields = self.fields()?;
+                Ok(Template::new(Box::new(Etuple::new(template_id, fields)?)))
+            },
+            "choice" => {
+                let variants = self.fields()?;
+                Ok(Template::new(Box::new(Choice::new(variants)?)))
+            },
+            "optional" => {
+                self.expect("(")?;
+                let template = self.template()?;
+                self.expect(")")?;
+                Ok(Template::new(Box::new(Optional::new(template))))
+            },
+            "default" => {
+                self.expect("(")?;
+                let template = self.template()?;
+                self.expect(",")?;
+                let default = self.default(&template)?;
+                self.expect(")")?;
+                Ok(Template::new(Box::new(Defaulted::new(template, default)?)))
+            },
+            _ => Err(Error::schema__parse__unknown_kind(position))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template::{Template, MAX_DEPTH};
+    use schema::{parse, print};
+    use error::Error;
+
+    fn assert_round_trip(text: &str, jinyang: &[u8]) {
+        let template = parse(text).unwrap();
+        assert_eq!(template.export_jinyang(), jinyang.to_vec());
+        assert_eq!(print(&template), text);
+        assert_eq!(print(&Template::from_jinyang(jinyang).unwrap()), text);
+    }
+
+    #[test]
+    fn should_round_trip_fixed() {
+        assert_round_trip("fixed(1)", &[0, 0]);
+        assert_round_trip("fixed(256)", &[0, 255]);
+        assert_round_trip("fixed(257)", &[1, 0, 0]);
+        assert_round_trip("fixed(65792)", &[1, 255, 255]);
+    }
+
+    #[test]
+    fn should_round_trip_dynamic() {
+        assert_round_trip("dynamic<=255", &[2]);
+        assert_round_trip("dynamic<=65535", &[3]);
+        assert_round_trip("dynamic<=16777215", &[4]);
+        assert_round_trip("dynamic<=4294967295", &[5]);
+    }
+
+    #[test]
+    fn should_round_trip_lists() {
+        assert_round_trip("dlist<=255(fixed(20))", &[6, 0, 19]);
+        assert_round_trip("dlist<=4294967295(dynamic<=65535)", &[9, 3]);
+        assert_round_trip("ilist<=65535(dlist<=16777215(fixed(300)))", &[11, 8, 1, 43, 0]);
+    }
+
+    #[test]
+    fn should_round_trip_tuples() {
+        assert_round_trip("tuple(fixed(1),dynamic<=255)", &[14, 2, 0, 0, 2, 0]);
+        assert_round_trip(
+            "tuple(id:fixed(1),\"say \\\"hi\\\"\":dynamic<=255)",
+            &[14, 2, 0, 0, 2, 1, 2, b'i', b'd', 0, 0, 0, 8, 0, b's', b'a', b'y', b' ', b'"', b'h', b'i', b'"']
+        );
+        let template = parse("tuple(\n  id \"Record id\" : fixed(1),\n  body: dynamic<=255\n)").unwrap();
+        assert_eq!(print(&template), "tuple(id\"Record id\":fixed(1),body:dynamic<=255)");
+    }
+
+    #[test]
+    fn should_round_trip_etuples() {
+        assert_round_trip("etuple<=65535(fixed(1),dynamic<=255)", &[16, 2, 0, 0, 2, 0, 0, 0]);
+        assert_round_trip(
+            "etuple<=255(id:fixed(2),tags:dynamic<=255=0x020a0b)",
+            &[15, 2, 0, 1, 2, 0, 1, 2, 10, 11, 1, 2, b'i', b'd', 0, 0, 4, b't', b'a', b'g', b's', 0, 0]
+        );
+        assert_eq!(parse("etuple<=255(fixed(1)=0x0)").err(), Some(Error::schema__parse__unexpected_character(24)));
+        assert_eq!(parse("etuple<=255(fixed(1)=0x0102)").err(), Some(Error::schema__parse__invalid_default(23)));
+        assert_eq!(parse("tuple(fixed(1)=0x01)").err(), Some(Error::tuple__new__fields_should_not_have_defaults));
+    }
+
+    #[test]
+    fn should_round_trip_choices() {
+        assert_round_trip("choice(fixed(1),dynamic<=255)", &[20, 2, 0, 0, 2, 0]);
+        assert_round_trip(
+            "choice(none:fixed(1),some:tuple(fixed(1)))",
+            &[20, 2, 0, 0, 14, 1, 0, 0, 0, 1, 4, b'n', b'o', b'n', b'e', 0, 0, 4, b's', b'o', b'm', b'e', 0, 0]
+        );
+    }
+
+    #[test]
+    fn should_round_trip_optionals() {
+        assert_round_trip("optional(fixed(1))", &[21, 0, 0]);
+        assert_round_trip("dlist<=255(optional(optional(dynamic<=255)))", &[6, 21, 21, 2]);
+    }
+
+    #[test]
+    fn should_ignore_whitespace() {
+        let template = parse(" dlist <= 255 (\n  fixed( 32 )\n) ").unwrap();
+        assert_eq!(print(&template), "dlist<=255(fixed(32))");
+    }
+
+    #[test]
+    fn should_error_when_invalid() {
+        assert_eq!(parse("union(fixed(1))").err(), Some(Error::schema__parse__unknown_kind(0)));
+        assert_eq!(parse("choice(fixed(1)=0x00)").err(), Some(Error::choice__new__variants_should_not_have_defaults));
+        assert_eq!(parse("tuple()").err(), Some(Error::schema__parse__unexpected_character(6)));
+        assert_eq!(parse("tuple(a:fixed(1),a:fixed(1))").err(), Some(Error::tuple__new__names_should_be_unique));
+        assert_eq!(parse("tuple(\"doc fixed(1))").err(), Some(Error::schema__parse__unexpected_end));
+        assert_eq!(parse("fixed(0)").err(), Some(Error::schema__parse__invalid_length(6)));
+        assert_eq!(parse("fixed(65793)").err(), Some(Error::schema__parse__invalid_length(6)));
+        assert_eq!(parse("dynamic<=1000").err(), Some(Error::schema__parse__invalid_length(9)));
+        assert_eq!(parse("dynamic<255").err(), Some(Error::schema__parse__unexpected_character(7)));
+        assert_eq!(parse("dlist<=255(fixed(1)").err(), Some(Error::schema__parse__unexpected_end));
+        assert_eq!(parse("fixed(1) fixed(1)").err(), Some(Error::schema__parse__should_not_have_any_remainder(9)));
+        assert_eq!(parse("").err(), Some(Error::schema__parse__unexpected_end));
+    }
+
+    #[test]
+    fn should_error_when_nested_too_deep() {
+        let text = format!("{}fixed(1){}", "dlist<=255(".repeat(MAX_DEPTH), ")".repeat(MAX_DEPTH));
+        assert!(parse(&text).is_ok());
+        let text = format!("tuple({})", text);
+        assert_eq!(
+            parse(&text).err(),
+            Some(Error::schema__parse__depth_should_be_lte_max_depth(6 + 11 * MAX_DEPTH))
+        );
+        assert_eq!(
+            parse(&"dlist<=255(".repeat(200000)).err(),
+            Some(Error::schema__parse__depth_should_be_lte_max_depth(11 * (MAX_DEPTH + 1)))
+        );
+    }
+
+}
@@ -0,0 +1,777 @@
+//! A serde `Serializer` and `Deserializer` that follow a template, behind
+//! the `serde` feature.
+//!
+//! Structs, tuples and tuple structs map to Tuple or Etuple fields in order,
+//! and struct field names must match any names the template gives. Sequences
+//! such as `Vec` map to Dlists and Ilists. Bytes, strings, `[u8; N]`,
+//! `Vec<u8>` and integers, which are little-endian, map to Fixed and Dynamic
+//! templates. Units are empty bytes, so they map to Dynamic. Newtype structs
+//! are transparent.
+//!
+//! `Option` maps to Optional. Enums map to Choices: the index of the Rust
+//! variant picks the Choice variant, and its name must match any name the
+//! template gives. The variant's value is the unit, newtype value, tuple or
+//! struct the Rust variant holds.
+//!
+//! Maps, floats and chars are `serialize__unsupported_type`.
+//!
+//! Deserializing ignores trailing Etuple fields with defaults that the Rust
+//! type does not have, as well as unknown Etuple data; any other child left
+//! over is `serialize__length_should_match_template`. Serializing fills
+//! fields the Rust type does not have with their defaults.
+
+use error::Error;
+use template::Template;
+use template_kind::TemplateKind;
+use encoders::tuple::Field;
+use encoders::choice::nest_variant;
+use nest::Nest;
+use serde::ser::{self, Serialize};
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Unexpected, Visitor};
+use std::fmt::Display;
+
+impl ser::Error for Error {
+    fn custom<T: Display>(message: T) -> Error {
+        Error::serialize__custom(message.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(message: T) -> Error {
+        Error::serialize__custom(message.to_string())
+    }
+}
+
+pub fn to_nest<T: Serialize + ?Sized>(template: &Template, value: &T) -> Result<Nest<'static>, Error> {
+    value.serialize(Serializer::new(template))
+}
+
+pub fn to_bytes<T: Serialize + ?Sized>(template: &Template, value: &T) -> Result<Vec<u8>, Error> {
+    template.encode(&to_nest(template, value)?)
+}
+
+pub fn from_nest<T: DeserializeOwned>(template: &Template, nest: &Nest) -> Result<T, Error> {
+    T::deserialize(Deserializer::new(template, nest))
+}
+
+pub fn from_bytes<T: DeserializeOwned>(template: &Template, bytes: &[u8]) -> Result<T, Error> {
+    from_nest(template, &template.decode(bytes)?)
+}
+
+fn is_bytes(template: &Template) -> bool {
+    matches!(template.kind(), TemplateKind::Fixed { .. } | TemplateKind::Dynamic { .. })
+}
+
+/// Serializes a value into a nest of `template`. The nest is not checked
+/// against the template's lengths; encoding it does that.
+pub struct Serializer<'t> {
+    template: &'t Template
+}
+
+impl<'t> Serializer<'t> {
+    pub fn new(template: &'t Template) -> Serializer<'t> {
+        Serializer { template }
+    }
+    fn bytes(self, bytes: Vec<u8>) -> Result<Nest<'static>, Error> {
+        if is_bytes(self.template) {
+            Ok(Nest::OwnedBytes(bytes))
+        } else {
+            Err(Error::serialize__type_should_match_template)
+        }
+    }
+    fn compound(self) -> Result<Compound<'t>, Error> {
+        Ok(Compound { template: self.template, nests: vec![], bytes: vec![] })
+    }
+    /// The Choice variant for the Rust variant at `index` named `name`.
+    fn variant(self, index: u32, name: &str) -> Result<&'t Field, Error> {
+        match self.template.kind() {
+            TemplateKind::Choice { variants } => {
+                let variant = variants.get(index as usize).ok_or(Error::serialize__variant_index_should_be_lt_variants_length)?;
+                if matches!(variant.name, Some(ref variant_name) if variant_name != name) {
+                    return Err(Error::serialize__variant_name_should_match_template);
+                }
+                Ok(variant)
+            },
+            _ => Err(Error::serialize__type_should_match_template)
+        }
+    }
+    fn variant_compound(self, index: u32, name: &str) -> Result<VariantCompound<'t>, Error> {
+        let variant = self.variant(index, name)?;
+        Ok(VariantCompound { index: index as u8, compound: Serializer::new(&variant.template).compound()? })
+    }
+}
+
+/// A Choice value: the variant byte, then the value.
+fn variant_nest(index: u32, value: Nest<'static>) -> Nest<'static> {
+    Nest::OwnedNests(vec![Nest::OwnedBytes(vec![index as u8]), value])
+}
+
+impl<'t> ser::Serializer for Serializer<'t> {
+    type Ok = Nest<'static>;
+    type Error = Error;
+    type SerializeSeq = Compound<'t>;
+    type SerializeTuple = Compound<'t>;
+    type SerializeTupleStruct = Compound<'t>;
+    type SerializeTupleVariant = VariantCompound<'t>;
+    type SerializeMap = ser::Impossible<Nest<'static>, Error>;
+    type SerializeStruct = Compound<'t>;
+    type SerializeStructVariant = VariantCompound<'t>;
+
+    fn serialize_bool(self, value: bool) -> Result<Nest<'static>, Error> {
+        self.bytes(vec![value as u8])
+    }
+    fn serialize_i8(self, value: i8) -> Result<Nest<'static>, Error> {
+        self.bytes(value.to_le_bytes().to_vec())
+    }
+    fn serialize_i16(self, value: i16) -> Result<Nest<'static>, Error> {
+        self.bytes(value.to_le_bytes().to_vec())
+    }
+    fn serialize_i32(self, value: i32) -> Result<Nest<'static>, Error> {
+        self.bytes(value.to_le_bytes().to_vec())
+    }
+    fn serialize_i64(self, value: i64) -> Result<Nest<'static>, Error> {
+        self.bytes(value.to_le_bytes().to_vec())
+    }
+    fn serialize_u8(self, value: u8) -> Result<Nest<'static>, Error> {
+        self.bytes(vec![value])
+    }
+    fn serialize_u16(self, value: u16) -> Result<Nest<'static>, Error> {
+        self.bytes(value.to_le_bytes().to_vec())
+    }
+    fn serialize_u32(self, value: u32) -> Result<Nest<'static>, Error> {
+        self.bytes(value.to_le_bytes().to_vec())
+    }
+    fn serialize_u64(self, value: u64) -> Result<Nest<'static>, Error> {
+        self.bytes(value.to_le_bytes().to_vec())
+    }
+    fn serialize_f32(self, _value: f32) -> Result<Nest<'static>, Error> {
+        Err(Error::serialize__unsupported_type)
+    }
+    fn serialize_f64(self, _value: f64) -> Result<Nest<'static>, Error> {
+        Err(Error::serialize__unsupported_type)
+    }
+    fn serialize_char(self, _value: char) -> Result<Nest<'static>, Error> {
+        Err(Error::serialize__unsupported_type)
+    }
+    fn serialize_str(self, value: &str) -> Result<Nest<'static>, Error> {
+        self.bytes(value.as_bytes().to_vec())
+    }
+    fn serialize_bytes(self, value: &[u8]) -> Result<Nest<'static>, Error> {
+        self.bytes(value.to_vec())
+    }
+    fn serialize_none(self) -> Result<Nest<'static>, Error> {
+        match self.template.kind() {
+            TemplateKind::Optional { .. } => Ok(Nest::OwnedNests(vec![])),
+            _ => Err(Error::serialize__type_should_match_template)
+        }
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Nest<'static>, Error> {
+        match self.template.kind() {
+            TemplateKind::Optional { template } => Ok(Nest::OwnedNests(vec![value.serialize(Serializer::new(template))?])),
+            _ => Err(Error::serialize__type_should_match_template)
+        }
+    }
+    fn serialize_unit(self) -> Result<Nest<'static>, Error> {
+        self.bytes(vec![])
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Nest<'static>, Error> {
+        self.bytes(vec![])
+    }
+    fn serialize_unit_variant(self, _name: &'static str, index: u32, variant: &'static str) -> Result<Nest<'static>, Error> {
+        let variant = self.variant(index, variant)?;
+        Ok(variant_nest(index, Serializer::new(&variant.template).serialize_unit()?))
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<Nest<'static>, Error> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, index: u32, variant: &'static str, value: &T) -> Result<Nest<'static>, Error> {
+        let variant = self.variant(index, variant)?;
+        Ok(variant_nest(index, value.serialize(Serializer::new(&variant.template))?))
+    }
+    fn serialize_seq(self, _length: Option<usize>) -> Result<Compound<'t>, Error> {
+        self.compound()
+    }
+    fn serialize_tuple(self, _length: usize) -> Result<Compound<'t>, Error> {
+        self.compound()
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, _length: usize) -> Result<Compound<'t>, Error> {
+        self.compound()
+    }
+    fn serialize_tuple_variant(self, _name: &'static str, index: u32, variant: &'static str, _length: usize) -> Result<VariantCompound<'t>, Error> {
+        self.variant_compound(index, variant)
+    }
+    fn serialize_map(self, _length: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error::serialize__unsupported_type)
+    }
+    fn serialize_struct(self, _name: &'static str, _length: usize) -> Result<Compound<'t>, Error> {
+        self.compound()
+    }
+    fn serialize_struct_variant(self, _name: &'static str, index: u32, variant: &'static str, _length: usize) -> Result<VariantCompound<'t>, Error> {
+        self.variant_compound(index, variant)
+    }
+}
+
+/// Collects the elements of a sequence, tuple or struct. Into a Fixed or
+/// Dynamic template, every element must serialize to a single byte.
+pub struct Compound<'t> {
+    template: &'t Template,
+    nests: Vec<Nest<'static>>,
+    bytes: Vec<u8>
+}
+
+impl<'t> Compound<'t> {
+    fn element<T: Serialize + ?Sized>(&mut self, name: Option<&str>, value: &T) -> Result<(), Error> {
+        match self.template.kind() {
+            TemplateKind::Fixed { .. } | TemplateKind::Dynamic { .. } => {
+                let nest = value.serialize(Serializer::new(self.template))?;
+                match (name, nest.bytes()) {
+                    (None, &[byte]) => self.bytes.push(byte),
+                    _ => return Err(Error::serialize__type_should_match_template)
+                }
+            },
+            TemplateKind::Dlist { template, .. } | TemplateKind::Ilist { template, .. } => {
+                if name.is_some() {
+                    return Err(Error::serialize__type_should_match_template);
+                }
+                self.nests.push(value.serialize(Serializer::new(template))?);
+            },
+            TemplateKind::Tuple { fields } | TemplateKind::Etuple { fields, .. } => {
+                let field = fields.get(self.nests.len()).ok_or(Error::serialize__length_should_match_template)?;
+                if let (Some(name), Some(field_name)) = (name, field.name.as_ref()) {
+                    if name != field_name {
+                        return Err(Error::serialize__field_name_should_match_template);
+                    }
+                }
+                self.nests.push(value.serialize(Serializer::new(&field.template))?);
+            },
+            TemplateKind::Choice { .. } | TemplateKind::Optional { .. } => return Err(Error::serialize__type_should_match_template)
+        }
+        Ok(())
+    }
+    fn end(mut self) -> Result<Nest<'static>, Error> {
+        match self.template.kind() {
+            TemplateKind::Fixed { .. } | TemplateKind::Dynamic { .. } => return Ok(Nest::OwnedBytes(self.bytes)),
+            TemplateKind::Dlist { .. } | TemplateKind::Ilist { .. } => {},
+            TemplateKind::Tuple { fields } | TemplateKind::Etuple { fields, .. } => {
+                for field in &fields[self.nests.len().min(fields.len())..] {
+                    let default = field.default_nest().ok_or(Error::serialize__length_should_match_template)?;
+                    self.nests.push(default);
+                }
+            },
+            TemplateKind::Choice { .. } | TemplateKind::Optional { .. } => return Err(Error::serialize__type_should_match_template)
+        }
+        Ok(Nest::OwnedNests(self.nests))
+    }
+}
+
+impl<'t> ser::SerializeSeq for Compound<'t> {
+    type Ok = Nest<'static>;
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(None, value)
+    }
+    fn end(self) -> Result<Nest<'static>, Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'t> ser::SerializeTuple for Compound<'t> {
+    type Ok = Nest<'static>;
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(None, value)
+    }
+    fn end(self) -> Result<Nest<'static>, Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'t> ser::SerializeTupleStruct for Compound<'t> {
+    type Ok = Nest<'static>;
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(None, value)
+    }
+    fn end(self) -> Result<Nest<'static>, Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'t> ser::SerializeStruct for Compound<'t> {
+    type Ok = Nest<'static>;
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, name: &'static str, value: &T) -> Result<(), Error> {
+        self.element(Some(name), value)
+    }
+    fn end(self) -> Result<Nest<'static>, Error> {
+        Compound::end(self)
+    }
+}
+
+/// Collects the fields of a tuple or struct variant into the template of
+/// its Choice variant.
+pub struct VariantCompound<'t> {
+    index: u8,
+    compound: Compound<'t>
+}
+
+impl<'t> ser::SerializeTupleVariant for VariantCompound<'t> {
+    type Ok = Nest<'static>;
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.compound.element(None, value)
+    }
+    fn end(self) -> Result<Nest<'static>, Error> {
+        Ok(variant_nest(self.index as u32, self.compound.end()?))
+    }
+}
+
+impl<'t> ser::SerializeStructVariant for VariantCompound<'t> {
+    type Ok = Nest<'static>;
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, name: &'static str, value: &T) -> Result<(), Error> {
+        self.compound.element(Some(name), value)
+    }
+    fn end(self) -> Result<Nest<'static>, Error> {
+        Ok(variant_nest(self.index as u32, self.compound.end()?))
+    }
+}
+
+/// Deserializes a value from a nest of `template`.
+pub struct Deserializer<'b, 'a: 'b> {
+    template: &'b Template,
+    nest: &'b Nest<'a>
+}
+
+impl<'b, 'a> Deserializer<'b, 'a> {
+    pub fn new(template: &'b Template, nest: &'b Nest<'a>) -> Deserializer<'b, 'a> {
+        Deserializer { template, nest }
+    }
+    fn bytes(&self) -> Result<&'b [u8], Error> {
+        if is_bytes(self.template) {
+            self.nest.try_bytes()
+        } else {
+            Err(Error::serialize__type_should_match_template)
+        }
+    }
+    /// The bytes of an integer or bool, which must fill the template.
+    fn bytes_of_length(&self, length: usize) -> Result<&'b [u8], Error> {
+        let bytes = self.bytes()?;
+        if bytes.len() == length {
+            Ok(bytes)
+        } else {
+            Err(Error::serialize__length_should_match_template)
+        }
+    }
+    /// Hands the children to `visitor`, which has to use all of them.
+    fn seq<'de, V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if is_bytes(self.template) {
+            let mut access = ByteAccess { bytes: self.bytes()?, index: 0 };
+            let value = visitor.visit_seq(&mut access)?;
+            if access.index < access.bytes.len() {
+                return Err(Error::serialize__length_should_match_template);
+            }
+            return Ok(value);
+        }
+        let kind = self.template.kind();
+        match kind {
+            TemplateKind::Fixed { .. } | TemplateKind::Dynamic { .. } => unreachable!(),
+            TemplateKind::Dlist { .. } | TemplateKind::Ilist { .. } => {},
+            TemplateKind::Tuple { fields } | TemplateKind::Etuple { fields, .. } => {
+                if self.nest.try_nests()?.len() < fields.len() {
+                    return Err(Error::serialize__length_should_match_template);
+                }
+            },
+            TemplateKind::Choice { .. } | TemplateKind::Optional { .. } => return Err(Error::serialize__type_should_match_template)
+        }
+        let mut access = NestAccess { kind, nests: self.nest.try_nests()?.collect(), index: 0 };
+        let value = visitor.visit_seq(&mut access)?;
+        if !access.is_used() {
+            return Err(Error::serialize__length_should_match_template);
+        }
+        Ok(value)
+    }
+}
+
+macro_rules! deserialize_integer {
+    ($method:ident, $visit:ident, $integer:ty) => {
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            let mut le_bytes = [0; ::std::mem::size_of::<$integer>()];
+            let bytes = self.bytes_of_length(le_bytes.len())?;
+            le_bytes.copy_from_slice(bytes);
+            visitor.$visit(<$integer>::from_le_bytes(le_bytes))
+        }
+    };
+}
+
+macro_rules! deserialize_unsupported {
+    ($($method:ident),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+                Err(Error::serialize__unsupported_type)
+            }
+        )*
+    };
+}
+
+impl<'de, 'b, 'a> de::Deserializer<'de> for Deserializer<'b, 'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if is_bytes(self.template) {
+            visitor.visit_bytes(self.bytes()?)
+        } else {
+            self.seq(visitor)
+        }
+    }
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.bytes_of_length(1)? {
+            [0] => visitor.visit_bool(false),
+            [1] => visitor.visit_bool(true),
+            bytes => Err(de::Error::invalid_value(Unexpected::Bytes(bytes), &visitor))
+        }
+    }
+    deserialize_integer!(deserialize_i8, visit_i8, i8);
+    deserialize_integer!(deserialize_i16, visit_i16, i16);
+    deserialize_integer!(deserialize_i32, visit_i32, i32);
+    deserialize_integer!(deserialize_i64, visit_i64, i64);
+    deserialize_integer!(deserialize_u8, visit_u8, u8);
+    deserialize_integer!(deserialize_u16, visit_u16, u16);
+    deserialize_integer!(deserialize_u32, visit_u32, u32);
+    deserialize_integer!(deserialize_u64, visit_u64, u64);
+    deserialize_unsupported!(
+        deserialize_f32, deserialize_f64, deserialize_char, deserialize_map, deserialize_identifier
+    );
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.template.kind() {
+            TemplateKind::Optional { template } => {
+                let mut nests = self.nest.try_nests()?;
+                match (nests.next(), nests.next()) {
+                    (None, _) => visitor.visit_none(),
+                    (Some(nest), None) => visitor.visit_some(Deserializer::new(template, nest)),
+                    (Some(_), Some(_)) => Err(Error::serialize__length_should_match_template)
+                }
+            },
+            _ => Err(Error::serialize__type_should_match_template)
+        }
+    }
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.bytes()?.is_empty() {
+            visitor.visit_unit()
+        } else {
+            Err(Error::serialize__length_should_match_template)
+        }
+    }
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let bytes = self.bytes()?;
+        match ::std::str::from_utf8(bytes) {
+            Ok(string) => visitor.visit_str(string),
+            Err(_) => Err(de::Error::invalid_value(Unexpected::Bytes(bytes), &visitor))
+        }
+    }
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_bytes(self.bytes()?)
+    }
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_bytes(self.bytes()?)
+    }
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.seq(visitor)
+    }
+    fn deserialize_tuple<V: Visitor<'de>>(self, _length: usize, visitor: V) -> Result<V::Value, Error> {
+        self.seq(visitor)
+    }
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, _length: usize, visitor: V) -> Result<V::Value, Error> {
+        self.seq(visitor)
+    }
+    fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str, names: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        match self.template.kind() {
+            TemplateKind::Tuple { fields } | TemplateKind::Etuple { fields, .. } => {
+                for (field, name) in fields.iter().zip(names) {
+                    if matches!(field.name, Some(ref field_name) if field_name != name) {
+                        return Err(Error::serialize__field_name_should_match_template);
+                    }
+                }
+                self.seq(visitor)
+            },
+            _ => Err(Error::serialize__type_should_match_template)
+        }
+    }
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, names: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        match self.template.kind() {
+            TemplateKind::Choice { variants } => {
+                for (variant, name) in variants.iter().zip(names) {
+                    if matches!(variant.name, Some(ref variant_name) if variant_name != name) {
+                        return Err(Error::serialize__variant_name_should_match_template);
+                    }
+                }
+                let index = nest_variant(self.nest).ok_or(Error::serialize__type_should_match_template)?;
+                let variant = variants.get(index).ok_or(Error::serialize__variant_index_should_be_lt_variants_length)?;
+                visitor.visit_enum(EnumAccess {
+                    index: index as u32,
+                    deserializer: Deserializer::new(&variant.template, self.nest.child(1)?)
+                })
+            },
+            _ => Err(Error::serialize__type_should_match_template)
+        }
+    }
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+}
+
+/// The variant of a Choice value, and a deserializer for its value.
+struct EnumAccess<'b, 'a: 'b> {
+    index: u32,
+    deserializer: Deserializer<'b, 'a>
+}
+
+impl<'de, 'b, 'a> de::EnumAccess<'de> for EnumAccess<'b, 'a> {
+    type Error = Error;
+    type Variant = Deserializer<'b, 'a>;
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Deserializer<'b, 'a>), Error> {
+        let variant = seed.deserialize(self.index.into_deserializer())?;
+        Ok((variant, self.deserializer))
+    }
+}
+
+impl<'de, 'b, 'a> de::VariantAccess<'de> for Deserializer<'b, 'a> {
+    type Error = Error;
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self)
+    }
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+    fn tuple_variant<V: Visitor<'de>>(self, _length: usize, visitor: V) -> Result<V::Value, Error> {
+        self.seq(visitor)
+    }
+    fn struct_variant<V: Visitor<'de>>(self, names: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_struct(self, "", names, visitor)
+    }
+}
+
+/// The children of a list or tuple. An Etuple's unknown data has no
+/// template, so it ends the sequence.
+struct NestAccess<'b, 'a: 'b> {
+    kind: TemplateKind<'b>,
+    nests: Vec<&'b Nest<'a>>,
+    index: usize
+}
+
+impl<'b, 'a> NestAccess<'b, 'a> {
+    /// Whether every child was visited, apart from Etuple fields that can be
+    /// left out and unknown data.
+    fn is_used(&self) -> bool {
+        match self.kind {
+            TemplateKind::Etuple { fields, .. } => fields.iter().skip(self.index)
+                .all(|field| field.default_nest().is_some()),
+            _ => self.index == self.nests.len()
+        }
+    }
+}
+
+impl<'de, 'b, 'a> de::SeqAccess<'de> for NestAccess<'b, 'a> {
+    type Error = Error;
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        match (self.nests.get(self.index), self.kind.child_template(self.index)) {
+            (Some(nest), Some(template)) => {
+                self.index += 1;
+                seed.deserialize(Deserializer::new(template, nest)).map(Some)
+            },
+            _ => Ok(None)
+        }
+    }
+}
+
+/// The bytes of a Fixed or Dynamic value, one `u8` at a time.
+struct ByteAccess<'b> {
+    bytes: &'b [u8],
+    index: usize
+}
+
+impl<'de, 'b> de::SeqAccess<'de> for ByteAccess<'b> {
+    type Error = Error;
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        match self.bytes.get(self.index) {
+            Some(&byte) => {
+                self.index += 1;
+                seed.deserialize(byte.into_deserializer()).map(Some)
+            },
+            None => Ok(None)
+        }
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.bytes.len() - self.index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template::Template;
+    use serialize::{to_bytes, from_bytes, to_nest};
+    use error::Error;
+    use nest::Nest;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Record {
+        id: [u8; 2],
+        version: u32,
+        body: String,
+        tags: Vec<Tag>
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Tag(Vec<u8>);
+
+    fn record() -> Record {
+        Record {
+            id: [1, 2],
+            version: 3,
+            body: "hi".to_string(),
+            tags: vec![Tag(vec![4]), Tag(vec![])]
+        }
+    }
+
+    #[test]
+    fn should_round_trip_structs() {
+        let template = Template::from_schema(
+            "tuple(id:fixed(2),version:fixed(4),body:dynamic<=255,tags:dlist<=255(dynamic<=255))"
+        ).unwrap();
+        let bytes = to_bytes(&template, &record()).unwrap();
+        assert_eq!(bytes, vec![1, 2, 3, 0, 0, 0, 2, 104, 105, 2, 1, 4, 0]);
+        assert_eq!(from_bytes(&template, &bytes), Ok(record()));
+    }
+
+    #[test]
+    fn should_follow_unnamed_fields_and_tuples() {
+        let template = Template::from_schema("tuple(fixed(2),fixed(4),dynamic<=255,ilist<=255(dynamic<=255))").unwrap();
+        let bytes = to_bytes(&template, &record()).unwrap();
+        assert_eq!(from_bytes(&template, &bytes), Ok(record()));
+        let template = Template::from_schema("tuple(fixed(1),dynamic<=255)").unwrap();
+        let bytes = to_bytes(&template, &(7u8, vec![8u8, 9])).unwrap();
+        assert_eq!(bytes, vec![7, 2, 8, 9]);
+        assert_eq!(from_bytes(&template, &bytes), Ok((7u8, vec![8u8, 9])));
+    }
+
+    #[test]
+    fn should_use_etuple_defaults_and_skip_new_fields() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct V1 {
+            a: u8
+        }
+        let v2 = Template::from_schema("etuple<=255(a:fixed(1),b:fixed(1)=0x05)").unwrap();
+        assert_eq!(to_nest(&v2, &V1 { a: 1 }), Ok(Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[5])])));
+        assert_eq!(from_bytes(&v2, &[3, 1, 6, 9]), Ok(V1 { a: 1 }));
+        let v2 = Template::from_schema("tuple(a:fixed(1),b:default(fixed(1),0x05))").unwrap();
+        assert_eq!(to_nest(&v2, &V1 { a: 1 }), Ok(Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[5])])));
+        let v2 = Template::from_schema("etuple<=255(a:fixed(1),b:default(fixed(1),0x05))").unwrap();
+        assert_eq!(to_nest(&v2, &V1 { a: 1 }), Ok(Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[5])])));
+        assert_eq!(from_bytes(&v2, &[2, 1, 6]), Ok(V1 { a: 1 }));
+    }
+
+    #[test]
+    fn should_error_when_children_are_left_over() {
+        let template = Template::from_schema("fixed(4)").unwrap();
+        assert_eq!(from_bytes::<[u8; 2]>(&template, &[1, 2, 3, 4]), Err(Error::serialize__length_should_match_template));
+        assert_eq!(from_bytes::<[u8; 4]>(&template, &[1, 2, 3, 4]), Ok([1, 2, 3, 4]));
+        let template = Template::from_schema("tuple(fixed(1),fixed(1),fixed(1))").unwrap();
+        assert_eq!(from_bytes::<(u8, u8)>(&template, &[1, 2, 3]), Err(Error::serialize__length_should_match_template));
+        let template = Template::from_schema("dlist<=255(fixed(1))").unwrap();
+        assert_eq!(from_bytes::<(u8, u8)>(&template, &[3, 1, 2, 3]), Err(Error::serialize__length_should_match_template));
+        let template = Template::from_schema("etuple<=255(fixed(1),fixed(1),fixed(1)=0x05)").unwrap();
+        assert_eq!(from_bytes::<(u8,)>(&template, &[3, 1, 2, 3]), Err(Error::serialize__length_should_match_template));
+        assert_eq!(from_bytes::<(u8, u8)>(&template, &[4, 1, 2, 3, 9]), Ok((1, 2)));
+    }
+
+    #[test]
+    fn should_error_when_type_does_not_match() {
+        let template = Template::from_schema("tuple(id:fixed(2),version:fixed(2),body:dynamic<=255,tags:dlist<=255(dynamic<=255))").unwrap();
+        assert_eq!(to_bytes(&template, &record()), Err(Error::fixed__encode_to__bytes_length_should_match_self_length));
+        assert_eq!(
+            from_bytes::<Record>(&template, &[1, 2, 3, 0, 0, 0]),
+            Err(Error::serialize__length_should_match_template)
+        );
+        let template = Template::from_schema("tuple(id:fixed(2),v:fixed(4),body:dynamic<=255,tags:dlist<=255(dynamic<=255))").unwrap();
+        assert_eq!(to_bytes(&template, &record()), Err(Error::serialize__field_name_should_match_template));
+        assert_eq!(from_bytes::<Record>(&template, &[0; 8]), Err(Error::serialize__field_name_should_match_template));
+        let template = Template::from_schema("dlist<=255(fixed(1))").unwrap();
+        assert_eq!(to_bytes(&template, &record()), Err(Error::serialize__type_should_match_template));
+        assert_eq!(to_bytes(&template, &"text"), Err(Error::serialize__type_should_match_template));
+        let template = Template::from_schema("tuple(fixed(1))").unwrap();
+        assert_eq!(to_bytes(&template, &(1u8, 2u8)), Err(Error::serialize__length_should_match_template));
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(u8),
+        Rect(u8, u8),
+        Labelled { id: u8, label: Option<String> }
+    }
+
+    fn shape() -> Template {
+        Template::from_schema(
+            "choice(Empty:dynamic<=255,Circle:fixed(1),Rect:tuple(fixed(1),fixed(1)),Labelled:tuple(id:fixed(1),label:optional(dynamic<=255)))"
+        ).unwrap()
+    }
+
+    #[test]
+    fn should_round_trip_options() {
+        let template = Template::from_schema("optional(fixed(1))").unwrap();
+        assert_eq!(to_bytes(&template, &Some(4u8)), Ok(vec![1, 4]));
+        assert_eq!(to_bytes(&template, &None::<u8>), Ok(vec![0]));
+        assert_eq!(from_bytes(&template, &[1, 4]), Ok(Some(4u8)));
+        assert_eq!(from_bytes(&template, &[0]), Ok(None::<u8>));
+        let template = Template::from_schema("fixed(1)").unwrap();
+        assert_eq!(to_bytes(&template, &Some(1u8)), Err(Error::serialize__type_should_match_template));
+        assert_eq!(from_bytes::<Option<u8>>(&template, &[1]), Err(Error::serialize__type_should_match_template));
+    }
+
+    #[test]
+    fn should_round_trip_enums() {
+        for (value, bytes) in [
+            (Shape::Empty, vec![0, 0]),
+            (Shape::Circle(5), vec![1, 5]),
+            (Shape::Rect(2, 3), vec![2, 2, 3]),
+            (Shape::Labelled { id: 7, label: Some("hi".to_string()) }, vec![3, 7, 1, 2, 104, 105]),
+            (Shape::Labelled { id: 7, label: None }, vec![3, 7, 0])
+        ] {
+            assert_eq!(to_bytes(&shape(), &value), Ok(bytes.clone()));
+            assert_eq!(from_bytes(&shape(), &bytes), Ok(value));
+        }
+        assert_eq!(to_bytes(&Template::from_schema("dynamic<=255").unwrap(), &()), Ok(vec![0]));
+    }
+
+    #[test]
+    fn should_error_when_variant_does_not_match() {
+        let template = Template::from_schema("choice(None:dynamic<=255,Circle:fixed(1))").unwrap();
+        assert_eq!(to_bytes(&template, &Shape::Empty), Err(Error::serialize__variant_name_should_match_template));
+        assert_eq!(from_bytes::<Shape>(&template, &[1, 5]), Err(Error::serialize__variant_name_should_match_template));
+        let template = Template::from_schema("choice(dynamic<=255,fixed(1))").unwrap();
+        assert_eq!(to_bytes(&template, &Shape::Circle(5)), Ok(vec![1, 5]));
+        assert_eq!(to_bytes(&template, &Shape::Rect(1, 2)), Err(Error::serialize__variant_index_should_be_lt_variants_length));
+        assert_eq!(from_bytes::<Shape>(&template, &[0, 1, 9]), Err(Error::serialize__length_should_match_template));
+        assert_eq!(to_bytes(&Template::from_schema("fixed(1)").unwrap(), &Shape::Circle(5)), Err(Error::serialize__type_should_match_template));
+        assert_eq!(from_bytes::<Shape>(&Template::from_schema("fixed(1)").unwrap(), &[0]), Err(Error::serialize__type_should_match_template));
+    }
+
+    #[test]
+    fn should_error_when_type_is_unsupported() {
+        let template = Template::from_schema("fixed(1)").unwrap();
+        assert_eq!(to_bytes(&template, &1.5f32), Err(Error::serialize__unsupported_type));
+        assert_eq!(to_bytes(&template, &'a'), Err(Error::serialize__unsupported_type));
+        assert_eq!(from_bytes::<char>(&template, &[1]), Err(Error::serialize__unsupported_type));
+    }
+
+}
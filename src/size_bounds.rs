@@ -0,0 +1,77 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SizeBounds {
+    pub min: usize,
+    /// `None` when the largest possible encoding does not fit in a `usize`.
+    pub max: Option<usize>,
+    /// Where the value's content starts, after any length prefix, when that
+    /// offset is the same for every value.
+    pub payload_offset: Option<usize>
+}
+
+impl SizeBounds {
+    pub fn is_constant(&self) -> bool {
+        self.max == Some(self.min)
+    }
+    pub fn contains(&self, length: usize) -> bool {
+        match self.max {
+            Some(max) => self.min <= length && length <= max,
+            None => self.min <= length
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template_ids::TemplateId;
+    use size_bounds::SizeBounds;
+    use fixtures::{fixed, dynamic, dlist};
+
+
+    #[test]
+    fn fixed_should_be_constant() {
+        let bounds = fixed(32).size_bounds();
+        assert_eq!(bounds, SizeBounds { min: 32, max: Some(32), payload_offset: Some(0) });
+        assert!(bounds.is_constant());
+    }
+
+    #[test]
+    fn dynamic_should_be_bounded_by_max_length() {
+        assert_eq!(
+            dynamic(TemplateId::DynamicAlpha).size_bounds(),
+            SizeBounds { min: 1, max: Some(256), payload_offset: Some(1) }
+        );
+        assert_eq!(
+            dynamic(TemplateId::DynamicBeta).size_bounds(),
+            SizeBounds { min: 2, max: Some(65537), payload_offset: Some(2) }
+        );
+        assert_eq!(
+            dynamic(TemplateId::DynamicDelta).size_bounds(),
+            SizeBounds { min: 4, max: Some(4294967299), payload_offset: Some(4) }
+        );
+        assert!(!dynamic(TemplateId::DynamicAlpha).size_bounds().is_constant());
+    }
+
+    #[test]
+    fn dlist_should_multiply_child_bounds() {
+        let bounds = dlist(TemplateId::DlistAlpha, fixed(20)).size_bounds();
+        assert_eq!(bounds, SizeBounds { min: 1, max: Some(1 + 255 * 20), payload_offset: Some(1) });
+        assert!(bounds.contains(1 + 3 * 20));
+        assert!(!bounds.contains(2 + 255 * 20));
+
+        let nested = dlist(
+            TemplateId::DlistBeta,
+            dlist(TemplateId::DlistAlpha, dynamic(TemplateId::DynamicAlpha))
+        );
+        assert_eq!(nested.size_bounds().max, Some(2 + 65535 * (1 + 255 * 256)));
+    }
+
+    #[test]
+    fn should_not_overflow() {
+        let bounds = dlist(TemplateId::DlistDelta, dynamic(TemplateId::DynamicDelta)).size_bounds();
+        assert_eq!(bounds.min, 4);
+        assert_eq!(bounds.max, None);
+        assert!(bounds.contains(usize::MAX));
+    }
+
+}
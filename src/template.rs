@@ -2,9 +2,28 @@ use error::Error;
 use traits::encoder::Encoder;
 use encoders::fixed::Fixed;
 use encoders::dynamic::Dynamic;
+use encoders::dlist::Dlist;
+use encoders::ilist::Ilist;
+use encoders::tuple::Tuple;
+use encoders::etuple::Etuple;
+use encoders::defaulted::Defaulted;
+use encoders::choice::Choice;
+use encoders::optional::Optional;
 use num_traits::FromPrimitive;
 use template_ids::TemplateId;
 use nest::Nest;
+use lazy::LazyNest;
+use patch;
+use size_bounds::SizeBounds;
+use template_kind::TemplateKind;
+use schema;
+use std::fmt;
+use sha2::{Sha256, Digest};
+use std::io::{BufRead, Write};
+
+/// How deep templates may nest. Deeper jinyang or schemas are rejected
+/// before they can exhaust the stack.
+pub const MAX_DEPTH: usize = 64;
 
 pub struct Template {
     encoder: Box<Encoder>
@@ -17,16 +36,28 @@ impl Template {
         }
     }
     pub fn from_jinyang<'a>(jinyang: &'a [u8]) -> Result<(Template), Error> {
-        Ok(Self::from_jinyang_with_remainder(jinyang).unwrap().0)
+        Ok(Self::from_jinyang_with_remainder(jinyang)?.0)
     }
     pub fn from_jinyang_with_remainder<'a>(jinyang: &'a [u8]) -> Result<(Template, &'a [u8]), Error> {
-        let template_id = TemplateId::from_u8(jinyang[0]).unwrap();
+        Self::from_jinyang_at_depth(jinyang, 0)
+    }
+    /// Reads a template nested `depth` levels inside another one, see
+    /// `MAX_DEPTH`.
+    pub fn from_jinyang_at_depth(jinyang: &[u8], depth: usize) -> Result<(Template, &[u8]), Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::template__from_jinyang__depth_should_be_lte_max_depth);
+        }
+        if jinyang.is_empty() {
+            return Err(Error::template__from_jinyang__unexpected_end);
+        }
+        let template_id = TemplateId::from_u8(jinyang[0])
+            .ok_or(Error::template__from_jinyang__invalid_template_id)?;
         match(template_id) {
             TemplateId::FixedAlpha | TemplateId::FixedBeta => {
                 let encoder_and_remainder = Fixed::from_jinyang_with_remainder(
                     template_id,
                     &jinyang[1..]
-                ).unwrap();
+                )?;
                 Ok((
                     Template::new(Box::new(encoder_and_remainder.0)),
                     encoder_and_remainder.1
@@ -39,31 +70,117 @@ impl Template {
                 let encoder_and_remainder = Dynamic::from_jinyang_with_remainder(
                     template_id,
                     &jinyang[1..]
-                ).unwrap();
+                )?;
                 Ok((
                     Template::new(Box::new(encoder_and_remainder.0)),
                     encoder_and_remainder.1
                 ))
             },
-            _ => {
-                panic!();
+            TemplateId::DlistAlpha |
+            TemplateId::DlistBeta |
+            TemplateId::DlistGamma |
+            TemplateId::DlistDelta => {
+                let (dlist, remainder) = Dlist::from_jinyang_with_remainder(
+                    template_id,
+                    &jinyang[1..],
+                    depth
+                )?;
+                Ok((Template::new(Box::new(dlist)), remainder))
+            },
+            TemplateId::IlistAlpha |
+            TemplateId::IlistBeta |
+            TemplateId::IlistGamma |
+            TemplateId::IlistDelta => {
+                let (ilist, remainder) = Ilist::from_jinyang_with_remainder(
+                    template_id,
+                    &jinyang[1..],
+                    depth
+                )?;
+                Ok((Template::new(Box::new(ilist)), remainder))
+            },
+            TemplateId::Tuple => {
+                let (tuple, remainder) = Tuple::from_jinyang_with_remainder(
+                    template_id,
+                    &jinyang[1..],
+                    depth
+                )?;
+                Ok((Template::new(Box::new(tuple)), remainder))
+            },
+            TemplateId::EtupleAlpha |
+            TemplateId::EtupleBeta |
+            TemplateId::EtupleGamma |
+            TemplateId::EtupleDelta => {
+                let (etuple, remainder) = Etuple::from_jinyang_with_remainder(
+                    template_id,
+                    &jinyang[1..],
+                    depth
+                )?;
+                Ok((Template::new(Box::new(etuple)), remainder))
+            },
+            TemplateId::Defaulted => {
+                let (defaulted, remainder) = Defaulted::from_jinyang_with_remainder(
+                    template_id,
+                    &jinyang[1..],
+                    depth
+                )?;
+                Ok((Template::new(Box::new(defaulted)), remainder))
+            },
+            TemplateId::Choice => {
+                let (choice, remainder) = Choice::from_jinyang_with_remainder(
+                    template_id,
+                    &jinyang[1..],
+                    depth
+                )?;
+                Ok((Template::new(Box::new(choice)), remainder))
+            },
+            TemplateId::Optional => {
+                let (optional, remainder) = Optional::from_jinyang_with_remainder(
+                    template_id,
+                    &jinyang[1..],
+                    depth
+                )?;
+                Ok((Template::new(Box::new(optional)), remainder))
             }
         }
     }
+    pub fn from_schema(text: &str) -> Result<Template, Error> {
+        schema::parse(text)
+    }
+    pub fn to_schema(&self) -> String {
+        schema::print(self)
+    }
     pub fn id(&self) -> u8 {
         self.encoder.template_id()
     }
+    pub fn kind(&self) -> TemplateKind<'_> {
+        self.encoder.kind()
+    }
     pub fn encoder(&self) -> &Box<Encoder> {
         &self.encoder
     }
+    pub fn size_bounds(&self) -> SizeBounds {
+        self.encoder.size_bounds()
+    }
+    /// The value to use when none is given, see `Encoder::default_nest`.
+    pub fn default_nest(&self) -> Nest<'static> {
+        self.encoder.default_nest()
+    }
+    pub fn encoded_len(&self, nest: &Nest) -> Result<usize, Error> {
+        self.encoder.encoded_len(nest)
+    }
     pub fn encode(&self, nest:&Nest) -> Result<Vec<u8>, Error> {
-        let mut encoding = vec![];
+        let mut encoding = Vec::with_capacity(self.encoded_len(nest)?);
         let result = self.encoder.encode_to(&nest, &mut encoding);
         match result {
             Err(error) => Err(error),
             Ok(_) => Ok(encoding)
         }
     }
+    /// Streams the same bytes `encode` would return into `to`, without
+    /// buffering the whole encoding in memory.
+    pub fn encode_to_writer<W: Write>(&self, nest: &Nest, to: &mut W) -> Result<(), Error> {
+        self.encoder.write_to(nest, to)
+    }
     pub fn decode<'a>(&self, bytes: &'a [u8]) -> Result<Nest<'a>, Error> {
         match self.encoder.decode_with_remainder(&bytes) {
             Err(error) => Err(error),
@@ -76,11 +193,104 @@ impl Template {
             }
         }
     }
+    /// Like `decode`, but Dlist children are only decoded when accessed.
+    pub fn decode_lazy<'t, 'a>(&'t self, bytes: &'a [u8]) -> Result<LazyNest<'t, 'a>, Error> {
+        let (lazy_nest, remainder) = LazyNest::decode_with_remainder(self, bytes)?;
+        if !remainder.is_empty() {
+            Err(Error::template__decode__should_not_have_any_remainder)
+        } else {
+            Ok(lazy_nest)
+        }
+    }
+    /// Reads the next value from a stream of back-to-back encodings. Returns
+    /// `Ok(None)` when the stream is exhausted before a value starts; running
+    /// out of bytes inside a value is `encoder__read_from__unexpected_eof`.
+    pub fn decode_from_reader<R: BufRead>(&self, from: &mut R) -> Result<Option<Nest<'static>>, Error> {
+        if from.fill_buf().map_err(Error::from_read_error)?.is_empty() {
+            Ok(None)
+        } else {
+            self.encoder.read_from(from).map(Some)
+        }
+    }
+    /// Checks that `bytes` start with a well-formed value and returns its
+    /// length. Unlike `decode`, trailing bytes are not an error.
+    pub fn validate(&self, bytes: &[u8]) -> Result<usize, Error> {
+        self.encoder.validate(bytes)
+    }
+    pub fn skip(&self, bytes: &[u8]) -> Result<usize, Error> {
+        self.encoder.skip(bytes)
+    }
+    /// Replaces the value at `path` inside `encoding` without re-encoding the
+    /// rest of it. Values that keep their length are overwritten in place;
+    /// otherwise the length fields on the path are adjusted. `encoding` is
+    /// left untouched when an error is returned.
+    pub fn patch(&self, encoding: &mut Vec<u8>, path: &[usize], nest: &Nest) -> Result<(), Error> {
+        let mut length_fields = vec![];
+        let (encoder, start) = self.encoder.locate(encoding, 0, path, &mut length_fields)?;
+        let old_length = encoder.skip(&encoding[start..])?;
+        let mut replacement = Vec::with_capacity(encoder.encoded_len(nest)?);
+        encoder.encode_to(nest, &mut replacement)?;
+        patch::splice(encoding, start, old_length, &replacement, &length_fields)
+    }
+    /// Renders `path` for people, using tuple field names where they exist:
+    /// `.items[2].id` rather than `[0][2][1]`. The value of a Choice depends
+    /// on its variant, so it is `[1]` and unnamed below; see
+    /// `nest_path_to_string`.
+    pub fn path_to_string(&self, path: &[usize]) -> String {
+        self.render_path(None, path)
+    }
+    /// Like `path_to_string`, but follows `nest` to name the values of
+    /// Choices after their variant: `.shape.circle` rather than `.shape[1]`.
+    pub fn nest_path_to_string(&self, nest: &Nest, path: &[usize]) -> String {
+        self.render_path(Some(nest), path)
+    }
+    fn render_path(&self, nest: Option<&Nest>, path: &[usize]) -> String {
+        if path.is_empty() {
+            return "[]".to_string();
+        }
+        let mut text = String::new();
+        let mut template = Some(self);
+        let mut nest = nest;
+        for index in path {
+            let kind = template.map(|template| template.kind());
+            let name = match nest {
+                Some(nest) => kind.and_then(|kind| kind.nest_child_name(nest, *index)),
+                None => kind.and_then(|kind| kind.child_name(*index))
+            };
+            match name {
+                Some(name) => {
+                    text.push('.');
+                    text.push_str(name);
+                },
+                None => text.push_str(&format!("[{}]", index))
+            }
+            template = match nest {
+                Some(nest) => kind.and_then(|kind| kind.nest_child_template(nest, *index)),
+                None => kind.and_then(|kind| kind.child_template(*index))
+            };
+            nest = nest.and_then(|nest| nest.child(*index).ok());
+        }
+        text
+    }
     pub fn export_jinyang(&self) -> Vec<u8> {
         let mut jinyang = vec![];
         self.export_jinyang_to(&mut jinyang);
         jinyang
     }
+    /// The jinyang without metadata, so templates that only differ in
+    /// field names or docs export the same bytes.
+    pub fn export_canonical_jinyang(&self) -> Vec<u8> {
+        let mut jinyang = vec![];
+        self.export_canonical_jinyang_to(&mut jinyang);
+        jinyang
+    }
+    /// SHA-256 of the canonical jinyang. Templates with the same fingerprint
+    /// produce and accept the same encodings.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        let mut fingerprint = [0; 32];
+        fingerprint.copy_from_slice(&Sha256::digest(self.export_canonical_jinyang()));
+        fingerprint
+    }
     pub fn encode_to<'a>(&self, nest: &Nest, to: &'a mut Vec<u8>) -> Result<(), Error> {
         to.push(self.encoder.template_id());
         self.encoder.encode_to(nest, to)
@@ -92,4 +302,23 @@ impl Template {
         to.push(self.encoder.template_id());
         self.encoder.export_jinyang_to(to);
     }
+    pub fn export_canonical_jinyang_to(&self, to: &mut Vec<u8>) {
+        if let Some(defaulted) = self.encoder.as_any().downcast_ref::<Defaulted>() {
+            return defaulted.template().export_canonical_jinyang_to(to);
+        }
+        to.push(self.encoder.template_id());
+        self.encoder.export_canonical_jinyang_to(to);
+    }
+}
+
+impl fmt::Debug for Template {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Template").field(&self.kind()).finish()
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_schema())
+    }
 }
@@ -10,4 +10,16 @@ pub enum TemplateId {
     DlistBeta =  7,
     DlistGamma =  8,
     DlistDelta =  9,
+    IlistAlpha =  10,
+    IlistBeta =  11,
+    IlistGamma =  12,
+    IlistDelta =  13,
+    Tuple =  14,
+    EtupleAlpha =  15,
+    EtupleBeta =  16,
+    EtupleGamma =  17,
+    EtupleDelta =  18,
+    Defaulted =  19,
+    Choice =  20,
+    Optional =  21,
 }
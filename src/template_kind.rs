@@ -0,0 +1,148 @@
+use template::Template;
+use encoders::tuple::Field;
+use encoders::choice::nest_variant;
+use nest::Nest;
+
+/// A template's parameters and child templates, so templates can be
+/// inspected without downcasting their encoders.
+#[derive(Debug, Clone, Copy)]
+pub enum TemplateKind<'t> {
+    Fixed {
+        length: usize
+    },
+    Dynamic {
+        length_encoding_length: usize,
+        max_length: usize
+    },
+    Dlist {
+        length_encoding_length: usize,
+        max_length: usize,
+        template: &'t Template
+    },
+    Ilist {
+        length_encoding_length: usize,
+        max_length: usize,
+        template: &'t Template
+    },
+    Tuple {
+        fields: &'t [Field]
+    },
+    Etuple {
+        length_encoding_length: usize,
+        max_length: usize,
+        fields: &'t [Field]
+    },
+    Choice {
+        variants: &'t [Field]
+    },
+    Optional {
+        template: &'t Template
+    }
+}
+
+impl<'t> TemplateKind<'t> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TemplateKind::Fixed { .. } => "fixed",
+            TemplateKind::Dynamic { .. } => "dynamic",
+            TemplateKind::Dlist { .. } => "dlist",
+            TemplateKind::Ilist { .. } => "ilist",
+            TemplateKind::Tuple { .. } => "tuple",
+            TemplateKind::Etuple { .. } => "etuple",
+            TemplateKind::Choice { .. } => "choice",
+            TemplateKind::Optional { .. } => "optional"
+        }
+    }
+    pub fn children(&self) -> Vec<&'t Template> {
+        match *self {
+            TemplateKind::Fixed { .. } | TemplateKind::Dynamic { .. } => vec![],
+            TemplateKind::Dlist { template, .. } | TemplateKind::Ilist { template, .. } | TemplateKind::Optional { template } => vec![template],
+            TemplateKind::Tuple { fields } | TemplateKind::Etuple { fields, .. } => fields.iter().map(|field| &field.template).collect(),
+            TemplateKind::Choice { variants } => variants.iter().map(|variant| &variant.template).collect()
+        }
+    }
+    /// The template of the child at `index`, if a value of this kind can
+    /// have one there. A Choice's value has the template of its variant, so
+    /// it needs `nest_child_template`.
+    pub fn child_template(&self, index: usize) -> Option<&'t Template> {
+        match *self {
+            TemplateKind::Fixed { .. } | TemplateKind::Dynamic { .. } | TemplateKind::Choice { .. } => None,
+            TemplateKind::Dlist { template, .. } | TemplateKind::Ilist { template, .. } => Some(template),
+            TemplateKind::Optional { template } => if index == 0 { Some(template) } else { None },
+            TemplateKind::Tuple { fields } | TemplateKind::Etuple { fields, .. } => fields.get(index).map(|field| &field.template)
+        }
+    }
+    /// The name of the child at `index`, for kinds whose children are named.
+    pub fn child_name(&self, index: usize) -> Option<&'t str> {
+        match *self {
+            TemplateKind::Tuple { fields } | TemplateKind::Etuple { fields, .. } => fields.get(index).and_then(|field| field.name.as_deref()),
+            _ => None
+        }
+    }
+    /// Like `child_template`, but for the child at `index` of `nest`, so a
+    /// Choice's value gets the template of the variant `nest` is set to.
+    pub fn nest_child_template(&self, nest: &Nest, index: usize) -> Option<&'t Template> {
+        match *self {
+            TemplateKind::Choice { variants } => nest_child_variant(variants, nest, index).map(|variant| &variant.template),
+            _ => self.child_template(index)
+        }
+    }
+    /// Like `child_name`, but names a Choice's value after its variant.
+    pub fn nest_child_name(&self, nest: &Nest, index: usize) -> Option<&'t str> {
+        match *self {
+            TemplateKind::Choice { variants } => nest_child_variant(variants, nest, index).and_then(|variant| variant.name.as_deref()),
+            _ => self.child_name(index)
+        }
+    }
+    /// Whether values of this kind are `Nest::Nests` rather than bytes.
+    pub fn is_composite(&self) -> bool {
+        !self.children().is_empty()
+    }
+}
+
+/// The variant of `nest` when `index` is its value.
+fn nest_child_variant<'t>(variants: &'t [Field], nest: &Nest, index: usize) -> Option<&'t Field> {
+    if index != 1 {
+        return None;
+    }
+    nest_variant(nest).and_then(|variant| variants.get(variant))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template::Template;
+    use template_kind::TemplateKind;
+
+    #[test]
+    fn should_describe_templates() {
+        let template = Template::from_jinyang(&[10, 1, 1, 0]).unwrap();
+        match template.kind() {
+            TemplateKind::Ilist { length_encoding_length, max_length, template } => {
+                assert_eq!(length_encoding_length, 1);
+                assert_eq!(max_length, 255);
+                match template.kind() {
+                    TemplateKind::Fixed { length } => assert_eq!(length, 258),
+                    _ => panic!()
+                }
+            },
+            _ => panic!()
+        }
+        assert_eq!(template.kind().name(), "ilist");
+        assert_eq!(template.kind().children().len(), 1);
+        assert!(template.kind().is_composite());
+        assert!(!template.kind().children()[0].kind().is_composite());
+    }
+
+    #[test]
+    fn should_describe_dynamics() {
+        match Template::from_jinyang(&[4]).unwrap().kind() {
+            TemplateKind::Dynamic { length_encoding_length, max_length } => {
+                assert_eq!(length_encoding_length, 3);
+                assert_eq!(max_length, 16777215);
+            },
+            _ => panic!()
+        }
+    }
+
+}
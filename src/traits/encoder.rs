@@ -1,11 +1,43 @@
 use error::Error;
 use std::any::Any;
+use std::io::{Read, Write};
 use nest::Nest;
+use size_bounds::SizeBounds;
+use patch::LengthField;
+use template_kind::TemplateKind;
 
 pub trait Encoder : Any {
     fn template_id(&self) -> u8;
+    fn kind(&self) -> TemplateKind<'_>;
     fn encode_to<'a>(&self, nest: &Nest, &'a mut Vec<u8>) -> Result<(), Error>;
+    /// Writes the same bytes as `encode_to`, but into any `Write`, so large
+    /// values never have to be buffered in memory.
+    fn write_to(&self, nest: &Nest, to: &mut dyn Write) -> Result<(), Error>;
+    /// The exact number of bytes `encode_to` would write, or the error it would return.
+    fn encoded_len(&self, nest: &Nest) -> Result<usize, Error>;
+    fn size_bounds(&self) -> SizeBounds;
+    /// The value used when none is given: zeros, empty, the defaults of the
+    /// children, or the default of a Choice's first variant.
+    fn default_nest(&self) -> Nest<'static>;
+    /// Reads exactly one value from `from`, pulling bytes on demand.
+    fn read_from(&self, from: &mut dyn Read) -> Result<Nest<'static>, Error>;
     fn decode_with_remainder<'a>(&self, bytes: &'a[u8]) -> Result<(Nest<'a>, &'a[u8]), Error>;
+    /// Checks `bytes` with the same rules as `decode_with_remainder` and
+    /// returns how many bytes the value consumed, without building a `Nest`.
+    fn validate(&self, bytes: &[u8]) -> Result<usize, Error>;
+    /// Measures how many bytes the next value occupies, checking only what is
+    /// needed to find its end.
+    fn skip(&self, bytes: &[u8]) -> Result<usize, Error>;
+    /// Finds the encoder and start of the value at `path` inside the value
+    /// starting at `start`, collecting the length fields that cover it.
+    /// Errors before slicing when `start` is past the end of `encoding`, so
+    /// the returned start is always within `encoding`.
+    fn locate(&self, encoding: &[u8], start: usize, path: &[usize], length_fields: &mut Vec<LengthField>) -> Result<(&dyn Encoder, usize), Error>;
     fn export_jinyang_to<'a>(&self, to: &'a mut Vec<u8>);
+    /// Like `export_jinyang_to`, but leaves out metadata such as tuple field
+    /// names, which has no effect on encodings.
+    fn export_canonical_jinyang_to(&self, to: &mut Vec<u8>) {
+        self.export_jinyang_to(to);
+    }
     fn as_any(&self) -> &dyn Any;
 }
@@ -0,0 +1,231 @@
+//! Test vectors shared with the implementations in other languages, behind
+//! the `json` feature. The vectors themselves live in `tests/vectors`.
+//!
+//! A vectors file is a JSON object with a `version` and a list of
+//! `vectors`. Each vector has a `name` and the hex of a `jinyang`, and
+//! exactly one of these shapes:
+//!
+//! - `nest` and `encoding`: the nest encodes to the encoding and the
+//!   encoding decodes to the nest.
+//! - `nest` and `error`: encoding the nest fails with the error.
+//! - `encoding` and `error`: decoding the encoding fails with the error.
+//! - `error` alone: reading the jinyang fails with the error.
+//!
+//! Nests are written as a hex string for bytes and an array for nests.
+//! Errors are named by their `Error` variant, without any fields, such as
+//! `fixed__encode_to__bytes_length_should_match_self_length`.
+
+use error::Error;
+use template::Template;
+use nest::Nest;
+use json::{bytes_to_json, bytes_from_json};
+use serde_json::{self, Value, Map};
+use std::fmt;
+
+/// The version of the vectors format this crate reads and writes.
+pub const VERSION: u64 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vector {
+    pub name: String,
+    pub jinyang: Vec<u8>,
+    pub nest: Option<Nest<'static>>,
+    pub encoding: Option<Vec<u8>>,
+    pub error: Option<String>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    /// The name of the vector that failed.
+    pub name: String,
+    pub description: String
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.description)
+    }
+}
+
+/// The name of `error` as vectors write it, which is its variant.
+pub fn error_name(error: &Error) -> String {
+    let debug = format!("{:?}", error);
+    debug.split('(').next().unwrap().to_string()
+}
+
+pub fn parse(text: &str) -> Result<Vec<Vector>, Error> {
+    let value: Value = serde_json::from_str(text).map_err(|_| Error::vectors__parse__invalid_json)?;
+    if value.get("version").and_then(Value::as_u64) != Some(VERSION) {
+        return Err(Error::vectors__parse__unsupported_version);
+    }
+    let vectors = value.get("vectors").and_then(Value::as_array).ok_or(Error::vectors__parse__invalid_json)?;
+    let mut parsed = Vec::with_capacity(vectors.len());
+    for (index, vector) in vectors.iter().enumerate() {
+        parsed.push(parse_vector(vector).ok_or(Error::vectors__parse__invalid_vector(index))?);
+    }
+    Ok(parsed)
+}
+
+fn parse_vector(value: &Value) -> Option<Vector> {
+    let vector = Vector {
+        name: value.get("name")?.as_str()?.to_string(),
+        jinyang: bytes_from_json(value.get("jinyang")?).ok()?,
+        nest: match value.get("nest") {
+            Some(nest) => Some(parse_nest(nest)?),
+            None => None
+        },
+        encoding: match value.get("encoding") {
+            Some(encoding) => Some(bytes_from_json(encoding).ok()?),
+            None => None
+        },
+        error: match value.get("error") {
+            Some(error) => Some(error.as_str()?.to_string()),
+            None => None
+        }
+    };
+    match (&vector.nest, &vector.encoding, &vector.error) {
+        (Some(_), Some(_), None) | (_, None, Some(_)) | (None, Some(_), Some(_)) => Some(vector),
+        _ => None
+    }
+}
+
+fn parse_nest(value: &Value) -> Option<Nest<'static>> {
+    match value {
+        Value::String(_) => bytes_from_json(value).ok().map(Nest::OwnedBytes),
+        Value::Array(values) => values.iter().map(parse_nest).collect::<Option<Vec<_>>>().map(Nest::OwnedNests),
+        _ => None
+    }
+}
+
+pub fn print(vectors: &[Vector]) -> String {
+    let vectors = vectors.iter().map(|vector| {
+        let mut object = Map::new();
+        object.insert("name".to_string(), Value::String(vector.name.clone()));
+        object.insert("jinyang".to_string(), bytes_to_json(&vector.jinyang));
+        if let Some(ref nest) = vector.nest {
+            object.insert("nest".to_string(), print_nest(nest));
+        }
+        if let Some(ref encoding) = vector.encoding {
+            object.insert("encoding".to_string(), bytes_to_json(encoding));
+        }
+        if let Some(ref error) = vector.error {
+            object.insert("error".to_string(), Value::String(error.clone()));
+        }
+        Value::Object(object)
+    }).collect();
+    let mut object = Map::new();
+    object.insert("version".to_string(), Value::from(VERSION));
+    object.insert("vectors".to_string(), Value::Array(vectors));
+    serde_json::to_string_pretty(&Value::Object(object)).unwrap()
+}
+
+fn print_nest(nest: &Nest) -> Value {
+    match nest {
+        Nest::Bytes(_) | Nest::OwnedBytes(_) => bytes_to_json(nest.bytes()),
+        Nest::Nests(_) | Nest::OwnedNests(_) => Value::Array(nest.iter_nests().map(print_nest).collect())
+    }
+}
+
+/// Checks every vector, returning those that fail.
+pub fn run(vectors: &[Vector]) -> Vec<Failure> {
+    vectors.iter().filter_map(|vector| {
+        check(vector).err().map(|description| Failure { name: vector.name.clone(), description })
+    }).collect()
+}
+
+fn check(vector: &Vector) -> Result<(), String> {
+    let expect_error = |result: Result<(), Error>| match (result, &vector.error) {
+        (Err(ref error), Some(expected)) if error_name(error) == *expected => Ok(()),
+        (Err(error), _) => Err(format!("failed with {}", error_name(&error))),
+        (Ok(()), _) => Err("should have failed".to_string())
+    };
+    let template = match Template::from_jinyang(&vector.jinyang) {
+        Ok(template) => template,
+        Err(error) => return expect_error(Err(error))
+    };
+    match (&vector.nest, &vector.encoding, &vector.error) {
+        (Some(nest), Some(encoding), None) => {
+            match template.encode(nest) {
+                Ok(ref actual) if actual == encoding => {},
+                Ok(_) => return Err("encodes to different bytes".to_string()),
+                Err(error) => return Err(format!("encode failed with {}", error_name(&error)))
+            }
+            match template.decode(encoding) {
+                Ok(ref actual) if actual == nest => Ok(()),
+                Ok(_) => Err("decodes to a different nest".to_string()),
+                Err(error) => Err(format!("decode failed with {}", error_name(&error)))
+            }
+        },
+        (Some(nest), None, Some(_)) => expect_error(template.encode(nest).map(|_| ())),
+        (None, Some(encoding), Some(_)) => expect_error(template.decode(encoding).map(|_| ())),
+        _ => expect_error(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use vectors::{parse, print, run, error_name, Vector, Failure};
+    use error::Error;
+    use nest::Nest;
+
+    const V1: &str = include_str!("../tests/vectors/v1.json");
+
+    #[test]
+    fn should_pass_every_vector() {
+        let vectors = parse(V1).unwrap();
+        assert_eq!(run(&vectors), vec![]);
+        for template_id in 0..22 {
+            assert!(vectors.iter().any(|vector| vector.jinyang.first() == Some(&template_id)));
+        }
+    }
+
+    #[test]
+    fn should_round_trip() {
+        let vectors = parse(V1).unwrap();
+        assert_eq!(parse(&print(&vectors)), Ok(vectors));
+    }
+
+    #[test]
+    fn should_report_failures() {
+        let vectors = vec![
+            Vector {
+                name: "wrong encoding".to_string(),
+                jinyang: vec![0, 0],
+                nest: Some(Nest::OwnedBytes(vec![1])),
+                encoding: Some(vec![2]),
+                error: None
+            },
+            Vector {
+                name: "wrong error".to_string(),
+                jinyang: vec![0, 0],
+                nest: None,
+                encoding: Some(vec![]),
+                error: Some("fixed__encode_to__bytes_length_should_match_self_length".to_string())
+            }
+        ];
+        assert_eq!(run(&vectors), vec![
+            Failure { name: "wrong encoding".to_string(), description: "encodes to different bytes".to_string() },
+            Failure {
+                name: "wrong error".to_string(),
+                description: "failed with fixed__decode_with_remainder__bytes_length_should_be_gte_self_length".to_string()
+            }
+        ]);
+    }
+
+    #[test]
+    fn should_error_when_invalid() {
+        assert_eq!(parse("{").err(), Some(Error::vectors__parse__invalid_json));
+        assert_eq!(parse("{\"version\":2,\"vectors\":[]}").err(), Some(Error::vectors__parse__unsupported_version));
+        assert_eq!(
+            parse("{\"version\":1,\"vectors\":[{\"name\":\"a\",\"jinyang\":\"0\"}]}").err(),
+            Some(Error::vectors__parse__invalid_vector(0))
+        );
+        assert_eq!(
+            parse("{\"version\":1,\"vectors\":[{\"name\":\"a\",\"jinyang\":\"00\",\"nest\":\"00\"}]}").err(),
+            Some(Error::vectors__parse__invalid_vector(0))
+        );
+        assert_eq!(error_name(&Error::schema__parse__invalid_default(3)), "schema__parse__invalid_default");
+    }
+
+}
@@ -0,0 +1,275 @@
+use error::Error;
+use template::Template;
+use nest::Nest;
+use nest_path::NestPath;
+use template_kind::TemplateKind;
+use encoders::choice::nest_variant;
+
+/// Callbacks for `walk`. Every method defaults to doing nothing, so a
+/// visitor only implements the ones it needs.
+pub trait Visitor<'a> {
+    fn enter(&mut self, _path: &NestPath, _template: &Template, _nest: &Nest<'a>) -> Result<(), Error> {
+        Ok(())
+    }
+    fn leave(&mut self, _path: &NestPath, _template: &Template, _nest: &Nest<'a>) -> Result<(), Error> {
+        Ok(())
+    }
+    fn visit_bytes(&mut self, _path: &NestPath, _template: &Template, _bytes: &[u8]) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Callbacks for `fold`, which rebuilds a nest bottom up. By default the
+/// nest is rebuilt unchanged.
+pub trait Folder<'a> {
+    fn fold_bytes(&mut self, _path: &NestPath, _template: &Template, nest: &Nest<'a>) -> Result<Nest<'a>, Error> {
+        Ok(nest.clone())
+    }
+    fn fold_nests(&mut self, _path: &NestPath, _template: &Template, nests: Vec<Nest<'a>>) -> Result<Nest<'a>, Error> {
+        Ok(Nest::OwnedNests(nests))
+    }
+}
+
+/// Checks that `nest` has the shape `template` expects: bytes for leaves,
+/// one child per field for tuples, plus the unknown data an Etuple may have
+/// kept, a known variant and its value for Choices, and at most one child
+/// for Optionals. The error names
+/// `path` with the field and variant names of `root` and `root_nest`.
+fn check_shape(root: &Template, root_nest: &Nest, path: &NestPath, template: &Template, nest: &Nest) -> Result<(), Error> {
+    let kind = template.kind();
+    let matches = match kind {
+        TemplateKind::Tuple { fields } => !nest.is_bytes() && nest.iter_nests().len() == fields.len(),
+        TemplateKind::Etuple { fields, .. } => !nest.is_bytes() && (
+            nest.iter_nests().len() == fields.len()
+            || (nest.iter_nests().len() == fields.len() + 1 && nest.iter_nests().last().unwrap().is_bytes())
+        ),
+        TemplateKind::Choice { variants } => matches!(nest_variant(nest), Some(variant) if variant < variants.len()),
+        TemplateKind::Optional { .. } => !nest.is_bytes() && nest.iter_nests().len() <= 1,
+        _ => kind.is_composite() != nest.is_bytes()
+    };
+    if matches {
+        Ok(())
+    } else {
+        Err(Error::visit__walk__nest_should_match_template(root.nest_path_to_string(root_nest, path)))
+    }
+}
+
+/// The template of the child at `index`, or `None` for an Etuple's unknown
+/// data and a Choice's variant byte, which are not visited and are kept as
+/// they are by `fold`.
+fn child_template<'t>(template: &'t Template, nest: &Nest, index: usize) -> Option<&'t Template> {
+    template.kind().nest_child_template(nest, index)
+}
+
+/// Visits `nest` depth first alongside the template it is encoded with.
+pub fn walk<'a, V: Visitor<'a>>(template: &Template, nest: &Nest<'a>, visitor: &mut V) -> Result<(), Error> {
+    walk_at(template, nest, &mut NestPath::root(), template, nest, visitor)
+}
+
+fn walk_at<'a, V: Visitor<'a>>(root: &Template, root_nest: &Nest, path: &mut NestPath, template: &Template, nest: &Nest<'a>, visitor: &mut V) -> Result<(), Error> {
+    check_shape(root, root_nest, path, template, nest)?;
+    if nest.is_bytes() {
+        return visitor.visit_bytes(path, template, nest.bytes());
+    }
+    visitor.enter(path, template, nest)?;
+    for (index, child) in nest.iter_nests().enumerate() {
+        if let Some(child_template) = child_template(template, nest, index) {
+            path.push(index);
+            walk_at(root, root_nest, path, child_template, child, visitor)?;
+            path.pop();
+        }
+    }
+    visitor.leave(path, template, nest)
+}
+
+pub fn fold<'a, F: Folder<'a>>(template: &Template, nest: &Nest<'a>, folder: &mut F) -> Result<Nest<'a>, Error> {
+    fold_at(template, nest, &mut NestPath::root(), template, nest, folder)
+}
+
+fn fold_at<'a, F: Folder<'a>>(root: &Template, root_nest: &Nest, path: &mut NestPath, template: &Template, nest: &Nest<'a>, folder: &mut F) -> Result<Nest<'a>, Error> {
+    check_shape(root, root_nest, path, template, nest)?;
+    if nest.is_bytes() {
+        return folder.fold_bytes(path, template, nest);
+    }
+    let mut nests = Vec::with_capacity(nest.iter_nests().len());
+    for (index, child) in nest.iter_nests().enumerate() {
+        match child_template(template, nest, index) {
+            Some(child_template) => {
+                path.push(index);
+                nests.push(fold_at(root, root_nest, path, child_template, child, folder)?);
+                path.pop();
+            },
+            None => nests.push(child.clone())
+        }
+    }
+    folder.fold_nests(path, template, nests)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use template_ids::TemplateId;
+    use template::Template;
+    use error::Error;
+    use nest::Nest;
+    use nest_path::NestPath;
+    use visit::{walk, fold, Visitor, Folder};
+    use fixtures::{fixed, dynamic, dlist};
+
+    fn template() -> Template {
+        dlist(TemplateId::DlistAlpha, dlist(TemplateId::DlistAlpha, dynamic(TemplateId::DynamicAlpha)))
+    }
+
+    struct Printer {
+        lines: Vec<String>
+    }
+
+    impl<'a> Visitor<'a> for Printer {
+        fn enter(&mut self, path: &NestPath, template: &Template, _nest: &Nest<'a>) -> Result<(), Error> {
+            self.lines.push(format!("{} enter {}", path, template.id()));
+            Ok(())
+        }
+        fn leave(&mut self, path: &NestPath, _template: &Template, _nest: &Nest<'a>) -> Result<(), Error> {
+            self.lines.push(format!("{} leave", path));
+            Ok(())
+        }
+        fn visit_bytes(&mut self, path: &NestPath, template: &Template, bytes: &[u8]) -> Result<(), Error> {
+            self.lines.push(format!("{} bytes {} {:?}", path, template.id(), bytes));
+            Ok(())
+        }
+    }
+
+    struct Redactor;
+
+    impl<'a> Folder<'a> for Redactor {
+        fn fold_bytes(&mut self, path: &NestPath, _template: &Template, nest: &Nest<'a>) -> Result<Nest<'a>, Error> {
+            if path.last() == Some(&0) {
+                Ok(Nest::OwnedBytes(vec![0; nest.bytes().len()]))
+            } else {
+                Ok(nest.clone())
+            }
+        }
+    }
+
+    #[test]
+    fn should_walk() {
+        let nest = Nest::Nests(&[
+            &Nest::Nests(&[&Nest::Bytes(&[1])]),
+            &Nest::Nests(&[])
+        ]);
+        let mut printer = Printer { lines: vec![] };
+        assert_eq!(walk(&template(), &nest, &mut printer), Ok(()));
+        assert_eq!(printer.lines, vec![
+            "[] enter 6",
+            "[0] enter 6",
+            "[0][0] bytes 2 [1]",
+            "[0] leave",
+            "[1] enter 6",
+            "[1] leave",
+            "[] leave"
+        ]);
+    }
+
+    #[test]
+    fn should_fold() {
+        let nest = Nest::Nests(&[
+            &Nest::Nests(&[&Nest::Bytes(&[1, 2]), &Nest::Bytes(&[3])])
+        ]);
+        assert_eq!(
+            fold(&template(), &nest, &mut Redactor),
+            Ok(Nest::Nests(&[
+                &Nest::Nests(&[&Nest::Bytes(&[0, 0]), &Nest::Bytes(&[3])])
+            ]))
+        );
+    }
+
+    #[test]
+    fn should_error_when_nest_does_not_match_template() {
+        let fixed = fixed(1);
+        let mut printer = Printer { lines: vec![] };
+        assert_eq!(
+            walk(&fixed, &Nest::Nests(&[]), &mut printer),
+            Err(Error::visit__walk__nest_should_match_template("[]".to_string()))
+        );
+        assert_eq!(
+            fold(&template(), &Nest::Nests(&[&Nest::Bytes(&[1])]), &mut Redactor),
+            Err(Error::visit__walk__nest_should_match_template("[0]".to_string()))
+        );
+        let tuple = Template::from_schema("tuple(fixed(1),dynamic<=255)").unwrap();
+        assert_eq!(
+            walk(&tuple, &Nest::Nests(&[&Nest::Bytes(&[1])]), &mut printer),
+            Err(Error::visit__walk__nest_should_match_template("[]".to_string()))
+        );
+        let tuple = Template::from_schema("tuple(id:fixed(1),tags:dlist<=255(tuple(name:dynamic<=255)))").unwrap();
+        let nest = Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Nests(&[&Nest::Nests(&[&Nest::Nests(&[])])])]);
+        assert_eq!(
+            walk(&tuple, &nest, &mut printer),
+            Err(Error::visit__walk__nest_should_match_template(".tags[0].name".to_string()))
+        );
+        assert_eq!(
+            fold(&tuple, &nest, &mut Redactor).err().map(|error| error.to_string()),
+            Some("visit__walk__nest_should_match_template(\".tags[0].name\")".to_string())
+        );
+    }
+
+    #[test]
+    fn should_keep_unknown_etuple_data() {
+        let etuple = Template::from_schema("etuple<=255(fixed(2))").unwrap();
+        let nest = Nest::Nests(&[&Nest::Bytes(&[1, 2]), &Nest::Bytes(&[3])]);
+        let mut printer = Printer { lines: vec![] };
+        walk(&etuple, &nest, &mut printer).unwrap();
+        assert_eq!(printer.lines, vec!["[] enter 15", "[0] bytes 0 [1, 2]", "[] leave"]);
+        assert_eq!(
+            fold(&etuple, &nest, &mut Redactor),
+            Ok(Nest::Nests(&[&Nest::Bytes(&[0, 0]), &Nest::Bytes(&[3])]))
+        );
+    }
+
+    #[test]
+    fn should_walk_tuples_with_field_templates() {
+        let tuple = Template::from_schema("tuple(fixed(1),dynamic<=255)").unwrap();
+        let mut printer = Printer { lines: vec![] };
+        walk(&tuple, &Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Bytes(&[2, 3])]), &mut printer).unwrap();
+        assert_eq!(printer.lines, vec![
+            "[] enter 14",
+            "[0] bytes 0 [1]",
+            "[1] bytes 2 [2, 3]",
+            "[] leave"
+        ]);
+    }
+
+    #[test]
+    fn should_walk_the_variant_of_choices() {
+        let choice = Template::from_schema("choice(circle:fixed(1),square:tuple(side:dynamic<=255))").unwrap();
+        let nest = Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Nests(&[&Nest::Bytes(&[4])])]);
+        let mut printer = Printer { lines: vec![] };
+        walk(&choice, &nest, &mut printer).unwrap();
+        assert_eq!(printer.lines, vec!["[] enter 20", "[1] enter 14", "[1][0] bytes 2 [4]", "[1] leave", "[] leave"]);
+        assert_eq!(
+            fold(&choice, &nest, &mut Redactor),
+            Ok(Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Nests(&[&Nest::Bytes(&[0])])]))
+        );
+        assert_eq!(
+            walk(&choice, &Nest::Nests(&[&Nest::Bytes(&[1]), &Nest::Nests(&[])]), &mut printer),
+            Err(Error::visit__walk__nest_should_match_template(".square".to_string()))
+        );
+        assert_eq!(
+            walk(&choice, &Nest::Nests(&[&Nest::Bytes(&[2]), &Nest::Bytes(&[4])]), &mut printer),
+            Err(Error::visit__walk__nest_should_match_template("[]".to_string()))
+        );
+    }
+
+    #[test]
+    fn should_walk_present_optionals() {
+        let optional = Template::from_schema("optional(fixed(1))").unwrap();
+        let mut printer = Printer { lines: vec![] };
+        walk(&optional, &Nest::Nests(&[&Nest::Bytes(&[4])]), &mut printer).unwrap();
+        walk(&optional, &Nest::Nests(&[]), &mut printer).unwrap();
+        assert_eq!(printer.lines, vec!["[] enter 21", "[0] bytes 0 [4]", "[] leave", "[] enter 21", "[] leave"]);
+        assert_eq!(
+            walk(&optional, &Nest::Nests(&[&Nest::Bytes(&[4]), &Nest::Bytes(&[5])]), &mut printer),
+            Err(Error::visit__walk__nest_should_match_template("[]".to_string()))
+        );
+    }
+
+}